@@ -2,24 +2,46 @@
 use axum::{
     extract::{Path, State},
     http::StatusCode,
+    response::sse::{Event, KeepAlive, Sse},
     routing::{get, post},
     Json, Router,
 };
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use futures::StreamExt;
+use russh::client::{self, Handle};
+use russh::ChannelMsg;
+use russh_keys::key::PublicKey;
+use russh_sftp::client::SftpSession;
+use rusqlite::{params, Connection, OptionalExtension};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::convert::Infallible;
 use std::process::Stdio;
 use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::process::Command;
-use tokio::time::{sleep, Duration, Instant};
+use tokio::sync::{broadcast, oneshot, Notify};
+use tokio::time::{Duration, Instant};
+use tokio_stream::wrappers::BroadcastStream;
 use tower_http::cors::{Any, CorsLayer};
 use tower_http::trace::TraceLayer;
-use tracing::{debug, error, info, warn};
+use tracing::{error, info, warn};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 use uuid::Uuid;
 use reqwest::Client;
 use chrono::{DateTime, Utc};
 use dotenv::dotenv;
 
+// Size of each chunk read from a child's stdout/stderr pipe while streaming.
+const OUTPUT_CHUNK_SIZE: usize = 8 * 1024;
+
+// A chunk of live output broadcast to `/result/:id/stream` subscribers.
+#[derive(Debug, Clone, Serialize)]
+struct OutputChunk {
+    stream: String,
+    data: String,
+}
+
 // Command request model
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct CommandRequest {
@@ -68,23 +90,40 @@ enum CommandStatus {
     Completed,
     Failed,
     TimedOut,
+    SshAuthFailed,
+    SshUnreachable,
+    Cancelled,
 }
 
-// Ngrok tunnel info
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct NgrokTunnel {
-    name: String,
-    uri: String,
-    public_url: String,
-    proto: String,
-    #[serde(rename = "config")]
-    tunnel_config: NgrokTunnelConfig,
+// Handler for the native SSH client. VMs are short-lived and exposed through ngrok tunnels that
+// hand out a fresh host key on every tunnel, so we don't pin or verify it.
+struct SshClientHandler;
+
+#[async_trait::async_trait]
+impl client::Handler for SshClientHandler {
+    type Error = russh::Error;
+
+    async fn check_server_key(
+        self,
+        _server_public_key: &PublicKey,
+    ) -> Result<(Self, bool), Self::Error> {
+        Ok((self, true))
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct NgrokTunnelConfig {
-    addr: String,
-    inspect: bool,
+// Why an SSH connection failed, so callers can surface it as a distinct `CommandStatus`.
+enum SshConnectError {
+    Unreachable(String),
+    AuthFailed(String),
+}
+
+impl SshConnectError {
+    fn into_message(self) -> String {
+        match self {
+            SshConnectError::Unreachable(e) => format!("VM unreachable over SSH: {}", e),
+            SshConnectError::AuthFailed(e) => format!("SSH authentication failed: {}", e),
+        }
+    }
 }
 
 // VM Manager response
@@ -100,12 +139,462 @@ struct VmResponse {
     ssh_password: String,
 }
 
+// One step of a scripted job, as submitted in a `POST /jobs` request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct JobStepRequest {
+    command: String,
+    working_directory: Option<String>,
+    environment: Option<HashMap<String, String>>,
+    timeout_seconds: Option<u64>,
+}
+
+// A job request: an ordered list of steps plus a continuation policy.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct JobRequest {
+    steps: Vec<JobStepRequest>,
+    // When true (the default), a failing step stops the remaining steps from running.
+    stop_on_failure: Option<bool>,
+}
+
+// The outcome of a single executed job step.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StepResult {
+    step_index: usize,
+    command: String,
+    status: CommandStatus,
+    stdout: Option<String>,
+    stderr: Option<String>,
+    exit_code: Option<i32>,
+    execution_time_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+enum JobStatus {
+    Pending,
+    Running,
+    Completed,
+    Failed,
+}
+
+// A multi-step scripted job, e.g. build -> test -> deploy in one request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Job {
+    id: String,
+    status: JobStatus,
+    steps: Vec<StepResult>,
+    stop_on_failure: bool,
+    created_at: DateTime<Utc>,
+    completed_at: Option<DateTime<Utc>>,
+}
+
+// Metadata for a file or directory entry, returned by the `/files` endpoints.
+#[derive(Debug, Clone, Serialize)]
+struct FileInfo {
+    path: String,
+    size: u64,
+    file_type: String,
+}
+
+// Request body for `POST /files/write`. `content_base64` is used (rather than a raw body) so
+// binary files survive JSON the same way the rest of this API's payloads are structured.
+#[derive(Debug, Clone, Deserialize)]
+struct FileWriteRequest {
+    path: String,
+    content_base64: String,
+    vm_id: Option<String>,
+    task_id: Option<String>,
+}
+
+// Query params for `GET /files/read`
+#[derive(Debug, Deserialize)]
+struct FileReadQuery {
+    path: String,
+    vm_id: Option<String>,
+    task_id: Option<String>,
+}
+
+// Response for `GET /files/read`: metadata plus base64-encoded content.
+#[derive(Debug, Serialize)]
+struct FileReadResponse {
+    path: String,
+    size: u64,
+    file_type: String,
+    content_base64: String,
+}
+
+// Query params for `GET /files/list`
+#[derive(Debug, Deserialize)]
+struct FileListQuery {
+    path: String,
+    vm_id: Option<String>,
+    task_id: Option<String>,
+}
+
+// A unit of work enqueued for a polling VM agent, returned by `GET /agent/work`.
+#[derive(Debug, Clone, Serialize)]
+struct AgentWorkItem {
+    id: String,
+    command: String,
+    working_directory: Option<String>,
+    environment: Option<HashMap<String, String>>,
+}
+
+// A VM agent's pending work queue and liveness, keyed by vm_id. Lets `execute_command_on_vm`
+// reach VMs that only poll out over HTTP instead of exposing an inbound SSH/ngrok endpoint.
+struct AgentState {
+    queue: VecDeque<AgentWorkItem>,
+    notify: Arc<Notify>,
+    last_seen: DateTime<Utc>,
+}
+
 // App state
 struct AppState {
-    command_results: Mutex<HashMap<String, CommandResult>>,
+    db: DbCtx,
+    output_channels: Mutex<HashMap<String, broadcast::Sender<OutputChunk>>>,
+    ssh_sessions: Mutex<HashMap<String, Arc<Handle<SshClientHandler>>>>,
+    cancel_signals: Mutex<HashMap<String, oneshot::Sender<()>>>,
+    // Commands handed off to a polling VM agent's queue instead of run over SSH. The agent has
+    // already pulled the work by the time a cancel request could arrive, and has no channel to
+    // receive one, so `cancel_command` checks this set to give those callers an honest error
+    // instead of a 202 that nothing will ever act on.
+    agent_routed_commands: Mutex<HashSet<String>>,
+    agents: Mutex<HashMap<String, AgentState>>,
     http_client: Client,
     vm_manager_url: String,
     ngrok_auth_token: String,
+    // Root directory the `/files/*` endpoints confine local (no vm_id/task_id) reads, writes,
+    // and listings to, so a caller-supplied `path` can't escape onto the rest of the host's
+    // filesystem.
+    files_root: std::path::PathBuf,
+}
+
+// Wraps the SQLite connection that persists command results, so history survives restarts and
+// can be queried across replicas instead of living only in an in-process HashMap.
+struct DbCtx {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl DbCtx {
+    fn new(path: &str) -> rusqlite::Result<Self> {
+        let conn = Connection::open(path)?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS commands (
+                id                 TEXT PRIMARY KEY,
+                task_id            TEXT,
+                vm_id              TEXT,
+                command            TEXT NOT NULL,
+                status             TEXT NOT NULL,
+                stdout             TEXT,
+                stderr             TEXT,
+                exit_code          INTEGER,
+                execution_time_ms  INTEGER,
+                created_at         TEXT NOT NULL,
+                completed_at       TEXT,
+                vm_details         TEXT
+            )",
+            [],
+        )?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS jobs (
+                id               TEXT PRIMARY KEY,
+                status           TEXT NOT NULL,
+                steps            TEXT NOT NULL,
+                stop_on_failure  INTEGER NOT NULL,
+                created_at       TEXT NOT NULL,
+                completed_at     TEXT
+            )",
+            [],
+        )?;
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    // Inserts a brand new command result (registered as Pending).
+    async fn insert_command(&self, result: &CommandResult) -> rusqlite::Result<()> {
+        let conn = self.conn.clone();
+        let result = result.clone();
+        tokio::task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            conn.execute(
+                "INSERT INTO commands (
+                    id, task_id, vm_id, command, status, stdout, stderr, exit_code,
+                    execution_time_ms, created_at, completed_at, vm_details
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+                params![
+                    result.id,
+                    result.task_id,
+                    result.vm_id,
+                    result.command,
+                    status_to_str(&result.status),
+                    result.stdout,
+                    result.stderr,
+                    result.exit_code,
+                    result.execution_time_ms,
+                    result.created_at.to_rfc3339(),
+                    result.completed_at.map(|t| t.to_rfc3339()),
+                    result
+                        .vm_details
+                        .as_ref()
+                        .map(|vm| serde_json::to_string(vm).unwrap()),
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+        .expect("db task panicked")
+    }
+
+    // Overwrites every column for `result.id` with its current state.
+    async fn update_command(&self, result: &CommandResult) -> rusqlite::Result<()> {
+        let conn = self.conn.clone();
+        let result = result.clone();
+        tokio::task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            conn.execute(
+                "UPDATE commands SET
+                    task_id = ?2, vm_id = ?3, status = ?4, stdout = ?5, stderr = ?6,
+                    exit_code = ?7, execution_time_ms = ?8, completed_at = ?9, vm_details = ?10
+                WHERE id = ?1",
+                params![
+                    result.id,
+                    result.task_id,
+                    result.vm_id,
+                    status_to_str(&result.status),
+                    result.stdout,
+                    result.stderr,
+                    result.exit_code,
+                    result.execution_time_ms,
+                    result.completed_at.map(|t| t.to_rfc3339()),
+                    result
+                        .vm_details
+                        .as_ref()
+                        .map(|vm| serde_json::to_string(vm).unwrap()),
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+        .expect("db task panicked")
+    }
+
+    async fn get_command(&self, id: &str) -> rusqlite::Result<Option<CommandResult>> {
+        let conn = self.conn.clone();
+        let id = id.to_string();
+        tokio::task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            conn.query_row(
+                "SELECT id, task_id, vm_id, command, status, stdout, stderr, exit_code,
+                        execution_time_ms, created_at, completed_at, vm_details
+                 FROM commands WHERE id = ?1",
+                params![id],
+                row_to_command_result,
+            )
+            .optional()
+        })
+        .await
+        .expect("db task panicked")
+    }
+
+    async fn count_commands(&self) -> rusqlite::Result<i64> {
+        let conn = self.conn.clone();
+        tokio::task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            conn.query_row("SELECT COUNT(*) FROM commands", [], |row| row.get(0))
+        })
+        .await
+        .expect("db task panicked")
+    }
+
+    // Backs `GET /results?task_id=&status=&limit=`.
+    async fn list_commands(
+        &self,
+        task_id: Option<String>,
+        status: Option<String>,
+        limit: i64,
+    ) -> rusqlite::Result<Vec<CommandResult>> {
+        let conn = self.conn.clone();
+        tokio::task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            // ?1/?2 are always bound; NULL makes the corresponding filter a no-op so the
+            // query text doesn't need to change based on which filters are present.
+            let mut stmt = conn.prepare(
+                "SELECT id, task_id, vm_id, command, status, stdout, stderr, exit_code,
+                        execution_time_ms, created_at, completed_at, vm_details
+                 FROM commands
+                 WHERE (?1 IS NULL OR task_id = ?1)
+                   AND (?2 IS NULL OR status = ?2)
+                 ORDER BY created_at DESC LIMIT ?3",
+            )?;
+            let rows = stmt.query_map(params![task_id, status, limit], row_to_command_result)?;
+            rows.collect()
+        })
+        .await
+        .expect("db task panicked")
+    }
+
+    // Inserts a brand new job (registered as Pending, with no steps yet).
+    async fn insert_job(&self, job: &Job) -> rusqlite::Result<()> {
+        let conn = self.conn.clone();
+        let job = job.clone();
+        tokio::task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            conn.execute(
+                "INSERT INTO jobs (id, status, steps, stop_on_failure, created_at, completed_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    job.id,
+                    job_status_to_str(&job.status),
+                    serde_json::to_string(&job.steps).unwrap(),
+                    job.stop_on_failure,
+                    job.created_at.to_rfc3339(),
+                    job.completed_at.map(|t| t.to_rfc3339()),
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+        .expect("db task panicked")
+    }
+
+    async fn update_job(&self, job: &Job) -> rusqlite::Result<()> {
+        let conn = self.conn.clone();
+        let job = job.clone();
+        tokio::task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            conn.execute(
+                "UPDATE jobs SET status = ?2, steps = ?3, completed_at = ?4 WHERE id = ?1",
+                params![
+                    job.id,
+                    job_status_to_str(&job.status),
+                    serde_json::to_string(&job.steps).unwrap(),
+                    job.completed_at.map(|t| t.to_rfc3339()),
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+        .expect("db task panicked")
+    }
+
+    async fn get_job(&self, id: &str) -> rusqlite::Result<Option<Job>> {
+        let conn = self.conn.clone();
+        let id = id.to_string();
+        tokio::task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap();
+            conn.query_row(
+                "SELECT id, status, steps, stop_on_failure, created_at, completed_at
+                 FROM jobs WHERE id = ?1",
+                params![id],
+                row_to_job,
+            )
+            .optional()
+        })
+        .await
+        .expect("db task panicked")
+    }
+}
+
+fn job_status_to_str(status: &JobStatus) -> String {
+    serde_json::to_value(status)
+        .unwrap()
+        .as_str()
+        .unwrap()
+        .to_string()
+}
+
+fn row_to_job(row: &rusqlite::Row) -> rusqlite::Result<Job> {
+    let status_str: String = row.get(1)?;
+    let steps_json: String = row.get(2)?;
+    let created_at: String = row.get(4)?;
+    let completed_at: Option<String> = row.get(5)?;
+
+    Ok(Job {
+        id: row.get(0)?,
+        status: serde_json::from_value(serde_json::Value::String(status_str)).unwrap(),
+        steps: serde_json::from_str(&steps_json).unwrap(),
+        stop_on_failure: row.get(3)?,
+        created_at: created_at.parse().unwrap(),
+        completed_at: completed_at.map(|t| t.parse().unwrap()),
+    })
+}
+
+// Fetches the current job for `id`, applies `mutate`, and writes the updated row back.
+async fn update_job_result<F>(state: &Arc<AppState>, id: &str, mutate: F)
+where
+    F: FnOnce(&mut Job),
+{
+    match state.db.get_job(id).await {
+        Ok(Some(mut job)) => {
+            mutate(&mut job);
+            if let Err(e) = state.db.update_job(&job).await {
+                error!("Failed to persist job {}: {}", id, e);
+            }
+        }
+        Ok(None) => warn!("Tried to update unknown job {}", id),
+        Err(e) => error!("Failed to load job {} for update: {}", id, e),
+    }
+}
+
+fn status_to_str(status: &CommandStatus) -> String {
+    serde_json::to_value(status)
+        .unwrap()
+        .as_str()
+        .unwrap()
+        .to_string()
+}
+
+fn row_to_command_result(row: &rusqlite::Row) -> rusqlite::Result<CommandResult> {
+    let status_str: String = row.get(4)?;
+    let created_at: String = row.get(9)?;
+    let completed_at: Option<String> = row.get(10)?;
+    let vm_details: Option<String> = row.get(11)?;
+
+    Ok(CommandResult {
+        id: row.get(0)?,
+        task_id: row.get(1)?,
+        vm_id: row.get(2)?,
+        command: row.get(3)?,
+        status: serde_json::from_value(serde_json::Value::String(status_str)).unwrap(),
+        stdout: row.get(5)?,
+        stderr: row.get(6)?,
+        exit_code: row.get(7)?,
+        execution_time_ms: row.get(8)?,
+        created_at: created_at.parse().unwrap(),
+        completed_at: completed_at.map(|t| t.parse().unwrap()),
+        vm_details: vm_details.map(|v| serde_json::from_str(&v).unwrap()),
+    })
+}
+
+// Fetches the current result for `id`, applies `mutate`, and writes the updated row back.
+// Mirrors the previous lock-then-mutate pattern over the in-memory HashMap, but against SQLite.
+async fn update_command_result<F>(state: &Arc<AppState>, id: &str, mutate: F)
+where
+    F: FnOnce(&mut CommandResult),
+{
+    match state.db.get_command(id).await {
+        Ok(Some(mut result)) => {
+            mutate(&mut result);
+            let reached_terminal_status = is_terminal_status(&result.status);
+            if let Err(e) = state.db.update_command(&result).await {
+                error!("Failed to persist command result {}: {}", id, e);
+            }
+            if reached_terminal_status {
+                // The broadcast channel and cancel signal only matter while a command is still
+                // in flight; leaving them in their maps past that point is an unbounded leak.
+                state.output_channels.lock().unwrap().remove(id);
+                state.cancel_signals.lock().unwrap().remove(id);
+            }
+        }
+        Ok(None) => warn!("Tried to update unknown command result {}", id),
+        Err(e) => error!("Failed to load command result {} for update: {}", id, e),
+    }
+}
+
+// Whether a command has reached a status it will never leave, i.e. it's safe to drop any
+// per-command bookkeeping (broadcast channels, cancel signals) keyed on its id.
+fn is_terminal_status(status: &CommandStatus) -> bool {
+    !matches!(status, CommandStatus::Pending | CommandStatus::Running)
 }
 
 // Main function
@@ -131,6 +620,16 @@ async fn main() {
             warn!("NGROK_AUTH_TOKEN not set. Command execution on VMs will be limited.");
             "".to_string()
         });
+    let db_path = std::env::var("COMMAND_EXECUTOR_DB_PATH")
+        .unwrap_or_else(|_| "command_executor.db".to_string());
+    let files_root = std::env::var("COMMAND_EXECUTOR_FILES_ROOT")
+        .unwrap_or_else(|_| "files".to_string());
+
+    // Open (or create) the SQLite database that persists command history
+    let db = DbCtx::new(&db_path).expect("Failed to open command history database");
+
+    std::fs::create_dir_all(&files_root).expect("Failed to create files root directory");
+    let files_root = std::fs::canonicalize(&files_root).expect("Failed to resolve files root directory");
 
     // Create HTTP client
     let http_client = Client::builder()
@@ -146,10 +645,16 @@ async fn main() {
 
     // Create shared state
     let state = Arc::new(AppState {
-        command_results: Mutex::new(HashMap::new()),
+        db,
+        output_channels: Mutex::new(HashMap::new()),
+        ssh_sessions: Mutex::new(HashMap::new()),
+        cancel_signals: Mutex::new(HashMap::new()),
+        agent_routed_commands: Mutex::new(HashSet::new()),
+        agents: Mutex::new(HashMap::new()),
         http_client,
         vm_manager_url,
         ngrok_auth_token,
+        files_root,
     });
 
     // Define routes
@@ -159,6 +664,16 @@ async fn main() {
         .route("/execute", post(execute_command))
         .route("/execute/vm", post(execute_command_on_vm))
         .route("/result/:id", get(get_command_result))
+        .route("/result/:id/stream", get(stream_command_result))
+        .route("/result/:id/cancel", post(cancel_command))
+        .route("/results", get(list_command_results))
+        .route("/jobs", post(execute_job))
+        .route("/jobs/:id", get(get_job_result))
+        .route("/agent/work", get(agent_poll_work))
+        .route("/agent/result/:id", post(agent_post_result))
+        .route("/files/write", post(write_file))
+        .route("/files/read", get(read_file))
+        .route("/files/list", get(list_files))
         .layer(TraceLayer::new_for_http())
         .layer(cors)
         .with_state(Arc::clone(&state));
@@ -175,17 +690,428 @@ async fn main() {
 
 // Health check handler
 async fn health_check(State(state): State<Arc<AppState>>) -> Json<serde_json::Value> {
-    let command_count = state.command_results.lock().unwrap().len();
-    
+    let command_count = state.db.count_commands().await.unwrap_or(0);
+
+    let agents: Vec<serde_json::Value> = state
+        .agents
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(vm_id, agent)| {
+            serde_json::json!({
+                "vm_id": vm_id,
+                "last_seen": agent.last_seen,
+                "queued_commands": agent.queue.len(),
+            })
+        })
+        .collect();
+
     Json(serde_json::json!({
         "status": "healthy",
         "version": env!("CARGO_PKG_VERSION"),
         "vm_manager_url": state.vm_manager_url,
         "has_ngrok_token": !state.ngrok_auth_token.is_empty(),
-        "command_count": command_count
+        "command_count": command_count,
+        "agents": agents
     }))
 }
 
+// Query params for `GET /results`
+#[derive(Debug, Deserialize)]
+struct ListResultsQuery {
+    task_id: Option<String>,
+    status: Option<String>,
+    limit: Option<i64>,
+}
+
+// Lists command history from the database, most recent first.
+async fn list_command_results(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Query(query): axum::extract::Query<ListResultsQuery>,
+) -> Result<Json<Vec<CommandResult>>, (StatusCode, String)> {
+    let limit = query.limit.unwrap_or(100);
+    state
+        .db
+        .list_commands(query.task_id, query.status, limit)
+        .await
+        .map(Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to query command history: {}", e)))
+}
+
+// Submits a multi-step scripted job, e.g. build -> test -> deploy in one request.
+async fn execute_job(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<JobRequest>,
+) -> Result<Json<Job>, (StatusCode, String)> {
+    if request.steps.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Job must have at least one step".to_string()));
+    }
+    for step in &request.steps {
+        if step.command.trim().is_empty() {
+            return Err((StatusCode::BAD_REQUEST, "Step command cannot be empty".to_string()));
+        }
+    }
+
+    let id = Uuid::new_v4().to_string();
+    let job = Job {
+        id: id.clone(),
+        status: JobStatus::Pending,
+        steps: Vec::new(),
+        stop_on_failure: request.stop_on_failure.unwrap_or(true),
+        created_at: Utc::now(),
+        completed_at: None,
+    };
+
+    if let Err(e) = state.db.insert_job(&job).await {
+        error!("Failed to persist new job: {}", e);
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, "Failed to record job".to_string()));
+    }
+
+    tokio::spawn(run_job_task(state.clone(), id.clone(), request));
+
+    Ok(Json(job))
+}
+
+// Get job result handler
+async fn get_job_result(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<Job>, (StatusCode, String)> {
+    match state.db.get_job(&id).await {
+        Ok(Some(job)) => Ok(Json(job)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "Job not found".to_string())),
+        Err(e) => {
+            error!("Failed to load job {}: {}", id, e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Failed to load job".to_string()))
+        }
+    }
+}
+
+// Background task that runs a job's steps sequentially, persisting progress after each one so
+// `GET /jobs/:id` reflects partial results while the job is still running.
+async fn run_job_task(state: Arc<AppState>, id: String, request: JobRequest) {
+    update_job_result(&state, &id, |job| {
+        job.status = JobStatus::Running;
+    })
+    .await;
+
+    let mut overall_failed = false;
+    let stop_on_failure = request.stop_on_failure.unwrap_or(true);
+
+    for (index, step) in request.steps.into_iter().enumerate() {
+        let step_id = format!("{}-step-{}", id, index);
+        let timeout = step.timeout_seconds.unwrap_or(60);
+        let start_time = Instant::now();
+
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(&step.command);
+        if let Some(dir) = &step.working_directory {
+            cmd.current_dir(dir);
+        }
+        if let Some(env) = &step.environment {
+            for (key, value) in env {
+                cmd.env(key, value);
+            }
+        }
+
+        // Steps aren't individually cancellable; the sender is held unused until the step ends,
+        // since dropping it early would resolve the receiver and read as a cancellation.
+        let (_cancel_tx, cancel_rx) = oneshot::channel();
+
+        info!("Executing job {} step {}: {}", id, index, step.command);
+        let (status, stdout, stderr, exit_code) =
+            match run_and_stream_command(&state, &step_id, cmd, timeout, cancel_rx).await {
+                Ok(outcome) => outcome,
+                Err(e) => {
+                    error!("Job {} step {} execution error: {}", id, index, e);
+                    (CommandStatus::Failed, None, Some(format!("Failed to execute command: {}", e)), Some(-1))
+                }
+            };
+
+        let step_failed = status != CommandStatus::Completed;
+        let step_result = StepResult {
+            step_index: index,
+            command: step.command.clone(),
+            status,
+            stdout,
+            stderr,
+            exit_code,
+            execution_time_ms: Some(start_time.elapsed().as_millis() as u64),
+        };
+
+        update_job_result(&state, &id, |job| {
+            job.steps.push(step_result);
+        })
+        .await;
+
+        if step_failed {
+            overall_failed = true;
+            if stop_on_failure {
+                break;
+            }
+        }
+    }
+
+    update_job_result(&state, &id, |job| {
+        job.status = if overall_failed { JobStatus::Failed } else { JobStatus::Completed };
+        job.completed_at = Some(Utc::now());
+    })
+    .await;
+}
+
+// Query params for `GET /agent/work`
+#[derive(Debug, Deserialize)]
+struct AgentWorkQuery {
+    vm_id: String,
+}
+
+// How long a poll blocks waiting for work before returning empty, so the agent's HTTP client
+// doesn't need an unbounded read timeout and reconnects periodically to report liveness.
+const AGENT_POLL_TIMEOUT_SECS: u64 = 30;
+
+// Long-polled by VM agents: blocks until a command is enqueued for `vm_id` or the poll times out.
+async fn agent_poll_work(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Query(query): axum::extract::Query<AgentWorkQuery>,
+) -> Json<Option<AgentWorkItem>> {
+    let notify = {
+        let mut agents = state.agents.lock().unwrap();
+        let agent = agents.entry(query.vm_id.clone()).or_insert_with(|| AgentState {
+            queue: VecDeque::new(),
+            notify: Arc::new(Notify::new()),
+            last_seen: Utc::now(),
+        });
+        agent.last_seen = Utc::now();
+        if let Some(item) = agent.queue.pop_front() {
+            return Json(Some(item));
+        }
+        agent.notify.clone()
+    };
+
+    // Wait for work to arrive, but don't block the agent forever; it will just poll again.
+    let _ = tokio::time::timeout(Duration::from_secs(AGENT_POLL_TIMEOUT_SECS), notify.notified()).await;
+
+    let mut agents = state.agents.lock().unwrap();
+    let item = agents
+        .get_mut(&query.vm_id)
+        .and_then(|agent| {
+            agent.last_seen = Utc::now();
+            agent.queue.pop_front()
+        });
+    Json(item)
+}
+
+// Body for `POST /agent/result/:id`, posted by a polling VM agent once it has run a command.
+#[derive(Debug, Deserialize)]
+struct AgentResultRequest {
+    status: CommandStatus,
+    stdout: Option<String>,
+    stderr: Option<String>,
+    exit_code: Option<i32>,
+}
+
+// Receives a command result pushed back by a polling VM agent and finishes updating the record
+// that `execute_command_on_vm_task` left in the `Running` state when it enqueued the work.
+async fn agent_post_result(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(request): Json<AgentResultRequest>,
+) -> StatusCode {
+    update_command_result(&state, &id, |result| {
+        let execution_time_ms = (Utc::now() - result.created_at).num_milliseconds().max(0) as u64;
+        result.status = request.status;
+        result.stdout = request.stdout;
+        result.stderr = request.stderr;
+        result.exit_code = request.exit_code;
+        result.execution_time_ms = Some(execution_time_ms);
+        result.completed_at = Some(Utc::now());
+    })
+    .await;
+
+    state.cancel_signals.lock().unwrap().remove(&id);
+    state.agent_routed_commands.lock().unwrap().remove(&id);
+    StatusCode::OK
+}
+
+// Confines a caller-supplied `requested` path to `root` for an existing file or directory. A
+// leading `/` is stripped, so an absolute path resolves inside `root`; `..` escapes are rejected
+// the same way `collect_glob_artifacts` (in the `command_executor` crate) rejects artifact globs
+// that escape their working_dir, by canonicalizing and checking the result still starts with
+// `root`.
+fn resolve_existing_local_path(root: &std::path::Path, requested: &str) -> Result<std::path::PathBuf, String> {
+    let candidate = root.join(requested.trim_start_matches('/'));
+    let candidate_canon =
+        std::fs::canonicalize(&candidate).map_err(|e| format!("Failed to resolve path: {}", e))?;
+    if !candidate_canon.starts_with(root) {
+        return Err("path escapes the configured files root".to_string());
+    }
+    Ok(candidate_canon)
+}
+
+// Same confinement as `resolve_existing_local_path`, but for a file that doesn't exist yet
+// (e.g. a write target): only the parent directory needs to already exist to be canonicalized.
+fn resolve_new_local_path(root: &std::path::Path, requested: &str) -> Result<std::path::PathBuf, String> {
+    let candidate = root.join(requested.trim_start_matches('/'));
+    let file_name = candidate.file_name().ok_or_else(|| "path has no file name".to_string())?;
+    let parent = candidate.parent().unwrap_or(root);
+    let parent_canon =
+        std::fs::canonicalize(parent).map_err(|e| format!("Failed to resolve parent directory: {}", e))?;
+    if !parent_canon.starts_with(root) {
+        return Err("path escapes the configured files root".to_string());
+    }
+    Ok(parent_canon.join(file_name))
+}
+
+// POST /files/write — writes a file locally, or over the VM's SFTP channel when vm_id/task_id
+// is set, using the same local-vs-VM routing as command execution. Lets an agent stage inputs
+// before running a command without base64-piping them through a shell command.
+async fn write_file(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<FileWriteRequest>,
+) -> Result<Json<FileInfo>, (StatusCode, String)> {
+    let content = BASE64
+        .decode(&request.content_base64)
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid base64 content: {}", e)))?;
+
+    if request.vm_id.is_some() || request.task_id.is_some() {
+        let vm_details = resolve_vm_for_files(&state, request.vm_id.clone(), request.task_id.clone())
+            .await
+            .map_err(|e| (StatusCode::BAD_GATEWAY, e))?;
+        let session = get_or_create_ssh_session(&state, &vm_details)
+            .await
+            .map_err(|e| (StatusCode::BAD_GATEWAY, e.into_message()))?;
+        let sftp = open_sftp_session(&session)
+            .await
+            .map_err(|e| (StatusCode::BAD_GATEWAY, e))?;
+
+        let mut file = sftp
+            .create(&request.path)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to create remote file: {}", e)))?;
+        file.write_all(&content)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to write remote file: {}", e)))?;
+    } else {
+        let target = resolve_new_local_path(&state.files_root, &request.path)
+            .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+        tokio::fs::write(&target, &content)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to write file: {}", e)))?;
+    }
+
+    Ok(Json(FileInfo {
+        path: request.path,
+        size: content.len() as u64,
+        file_type: "file".to_string(),
+    }))
+}
+
+// GET /files/read?path=&vm_id= — reads a file locally, or over the VM's SFTP channel when
+// vm_id/task_id is set, returning its content base64-encoded alongside basic metadata.
+async fn read_file(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Query(query): axum::extract::Query<FileReadQuery>,
+) -> Result<Json<FileReadResponse>, (StatusCode, String)> {
+    let content = if query.vm_id.is_some() || query.task_id.is_some() {
+        let vm_details = resolve_vm_for_files(&state, query.vm_id.clone(), query.task_id.clone())
+            .await
+            .map_err(|e| (StatusCode::BAD_GATEWAY, e))?;
+        let session = get_or_create_ssh_session(&state, &vm_details)
+            .await
+            .map_err(|e| (StatusCode::BAD_GATEWAY, e.into_message()))?;
+        let sftp = open_sftp_session(&session)
+            .await
+            .map_err(|e| (StatusCode::BAD_GATEWAY, e))?;
+
+        let mut file = sftp
+            .open(&query.path)
+            .await
+            .map_err(|e| (StatusCode::NOT_FOUND, format!("Failed to open remote file: {}", e)))?;
+        let mut content = Vec::new();
+        file.read_to_end(&mut content)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to read remote file: {}", e)))?;
+        content
+    } else {
+        let target = resolve_existing_local_path(&state.files_root, &query.path)
+            .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+        tokio::fs::read(&target)
+            .await
+            .map_err(|e| (StatusCode::NOT_FOUND, format!("Failed to read file: {}", e)))?
+    };
+
+    Ok(Json(FileReadResponse {
+        size: content.len() as u64,
+        content_base64: BASE64.encode(&content),
+        path: query.path,
+        file_type: "file".to_string(),
+    }))
+}
+
+// GET /files/list?path= — lists a directory locally, or over the VM's SFTP channel when
+// vm_id/task_id is set.
+async fn list_files(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Query(query): axum::extract::Query<FileListQuery>,
+) -> Result<Json<Vec<FileInfo>>, (StatusCode, String)> {
+    if query.vm_id.is_some() || query.task_id.is_some() {
+        let vm_details = resolve_vm_for_files(&state, query.vm_id.clone(), query.task_id.clone())
+            .await
+            .map_err(|e| (StatusCode::BAD_GATEWAY, e))?;
+        let session = get_or_create_ssh_session(&state, &vm_details)
+            .await
+            .map_err(|e| (StatusCode::BAD_GATEWAY, e.into_message()))?;
+        let sftp = open_sftp_session(&session)
+            .await
+            .map_err(|e| (StatusCode::BAD_GATEWAY, e))?;
+
+        let base = query.path.trim_end_matches('/').to_string();
+        let entries = sftp
+            .read_dir(&query.path)
+            .await
+            .map_err(|e| (StatusCode::NOT_FOUND, format!("Failed to list remote directory: {}", e)))?;
+
+        Ok(Json(
+            entries
+                .map(|entry| FileInfo {
+                    path: format!("{}/{}", base, entry.file_name()),
+                    size: entry.metadata().size.unwrap_or(0),
+                    file_type: if entry.metadata().is_dir() {
+                        "directory".to_string()
+                    } else {
+                        "file".to_string()
+                    },
+                })
+                .collect(),
+        ))
+    } else {
+        let target = resolve_existing_local_path(&state.files_root, &query.path)
+            .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+        let mut read_dir = tokio::fs::read_dir(&target)
+            .await
+            .map_err(|e| (StatusCode::NOT_FOUND, format!("Failed to list directory: {}", e)))?;
+
+        let mut entries = Vec::new();
+        while let Some(entry) = read_dir
+            .next_entry()
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to read directory entry: {}", e)))?
+        {
+            let metadata = entry
+                .metadata()
+                .await
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to read file metadata: {}", e)))?;
+            entries.push(FileInfo {
+                path: entry.path().to_string_lossy().to_string(),
+                size: metadata.len(),
+                file_type: if metadata.is_dir() { "directory".to_string() } else { "file".to_string() },
+            });
+        }
+
+        Ok(Json(entries))
+    }
+}
+
 // Execute command handler (local execution)
 async fn execute_command(
     State(state): State<Arc<AppState>>,
@@ -215,16 +1141,30 @@ async fn execute_command(
         vm_details: None,
     };
     
+    if let Err(e) = state.db.insert_command(&command_result).await {
+        error!("Failed to persist new command result: {}", e);
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, "Failed to record command".to_string()));
+    }
+
+    // Create the broadcast channel callers can subscribe to via /result/:id/stream
     {
-        let mut results = state.command_results.lock().unwrap();
-        results.insert(id.clone(), command_result.clone());
+        let (tx, _rx) = broadcast::channel(256);
+        state.output_channels.lock().unwrap().insert(id.clone(), tx);
     }
-    
+
+    // Register a cancellation signal that /result/:id/cancel can trigger
+    let cancel_rx = {
+        let (cancel_tx, cancel_rx) = oneshot::channel();
+        state.cancel_signals.lock().unwrap().insert(id.clone(), cancel_tx);
+        cancel_rx
+    };
+
     // Execute command in background
     tokio::spawn(execute_command_task(
         state.clone(),
         id.clone(),
         request,
+        cancel_rx,
     ));
     
     // Return the pending result
@@ -265,16 +1205,30 @@ async fn execute_command_on_vm(
         vm_details: None,
     };
     
+    if let Err(e) = state.db.insert_command(&command_result).await {
+        error!("Failed to persist new command result: {}", e);
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, "Failed to record command".to_string()));
+    }
+
+    // Create the broadcast channel callers can subscribe to via /result/:id/stream
     {
-        let mut results = state.command_results.lock().unwrap();
-        results.insert(id.clone(), command_result.clone());
+        let (tx, _rx) = broadcast::channel(256);
+        state.output_channels.lock().unwrap().insert(id.clone(), tx);
     }
-    
+
+    // Register a cancellation signal that /result/:id/cancel can trigger
+    let cancel_rx = {
+        let (cancel_tx, cancel_rx) = oneshot::channel();
+        state.cancel_signals.lock().unwrap().insert(id.clone(), cancel_tx);
+        cancel_rx
+    };
+
     // Execute command in background
     tokio::spawn(execute_command_on_vm_task(
         state.clone(),
         id.clone(),
         request,
+        cancel_rx,
     ));
     
     // Return the pending result
@@ -286,11 +1240,67 @@ async fn get_command_result(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
 ) -> Result<Json<CommandResult>, (StatusCode, String)> {
-    let results = state.command_results.lock().unwrap();
-    
-    match results.get(&id) {
-        Some(result) => Ok(Json(result.clone())),
-        None => Err((StatusCode::NOT_FOUND, "Command result not found".to_string())),
+    match state.db.get_command(&id).await {
+        Ok(Some(result)) => Ok(Json(result)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "Command result not found".to_string())),
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to query command result: {}", e))),
+    }
+}
+
+// Stream a command's stdout/stderr as Server-Sent Events as it is produced.
+// Subscribers that connect after the command finished still get nothing new here;
+// the final output remains available via `get_command_result`.
+async fn stream_command_result(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Sse<impl futures::Stream<Item = Result<Event, Infallible>>>, (StatusCode, String)> {
+    match state.db.get_command(&id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return Err((StatusCode::NOT_FOUND, "Command result not found".to_string())),
+        Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to query command result: {}", e))),
+    }
+
+    let rx = {
+        let mut channels = state.output_channels.lock().unwrap();
+        channels
+            .entry(id.clone())
+            .or_insert_with(|| broadcast::channel(256).0)
+            .subscribe()
+    };
+
+    let stream = BroadcastStream::new(rx).filter_map(|chunk| async move {
+        match chunk {
+            Ok(chunk) => Some(Ok(Event::default().event(chunk.stream).data(chunk.data))),
+            Err(_) => None,
+        }
+    });
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+// Cancel a running command. Returns 404 if the command is unknown or already finished.
+async fn cancel_command(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    if state.agent_routed_commands.lock().unwrap().contains(&id) {
+        return Err((
+            StatusCode::CONFLICT,
+            "Command was routed to a polling VM agent and cannot be cancelled".to_string(),
+        ));
+    }
+
+    let sender = state.cancel_signals.lock().unwrap().remove(&id);
+
+    match sender {
+        Some(sender) => {
+            let _ = sender.send(());
+            Ok(StatusCode::ACCEPTED)
+        }
+        None => Err((
+            StatusCode::NOT_FOUND,
+            "Command is not running or does not exist".to_string(),
+        )),
     }
 }
 
@@ -299,95 +1309,67 @@ async fn execute_command_task(
     state: Arc<AppState>,
     id: String,
     request: CommandRequest,
+    cancel_rx: oneshot::Receiver<()>,
 ) {
     let timeout = request.timeout_seconds.unwrap_or(60);
     let start_time = Instant::now();
-    
+
     // Update status to running
-    {
-        let mut results = state.command_results.lock().unwrap();
-        if let Some(result) = results.get_mut(&id) {
-            result.status = CommandStatus::Running;
-        }
-    }
-    
+    update_command_result(&state, &id, |result| {
+        result.status = CommandStatus::Running;
+    })
+    .await;
+
     // Build command
     let mut cmd = Command::new("sh");
     cmd.arg("-c").arg(&request.command);
-    
+
     // Set working directory if specified
     if let Some(dir) = &request.working_directory {
         cmd.current_dir(dir);
     }
-    
+
     // Set environment variables if specified
     if let Some(env) = &request.environment {
         for (key, value) in env {
             cmd.env(key, value);
         }
     }
-    
-    // Configure stdio
-    cmd.stdout(Stdio::piped());
-    cmd.stderr(Stdio::piped());
-    
-    // Execute command with timeout
+
+    // Execute command with timeout, streaming output live as it is produced
     info!("Executing command: {}", request.command);
-    let result = tokio::time::timeout(Duration::from_secs(timeout), cmd.spawn().unwrap().wait_with_output()).await;
-    
-    // Process result
+    let (status, stdout, stderr, exit_code) =
+        match run_and_stream_command(&state, &id, cmd, timeout, cancel_rx).await {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                error!("Command execution error: {}", e);
+                (CommandStatus::Failed, None, Some(format!("Failed to execute command: {}", e)), Some(-1))
+            }
+        };
+
     let execution_time = start_time.elapsed();
-    let status;
-    let stdout;
-    let stderr;
-    let exit_code;
-    
-    match result {
-        Ok(Ok(output)) => {
-            stdout = Some(String::from_utf8_lossy(&output.stdout).to_string());
-            stderr = Some(String::from_utf8_lossy(&output.stderr).to_string());
-            exit_code = output.status.code();
-            status = if output.status.success() {
-                CommandStatus::Completed
-            } else {
-                CommandStatus::Failed
-            };
-            
-            info!(
-                "Command completed with exit code {:?} in {:?}",
-                exit_code, execution_time
-            );
-        }
-        Ok(Err(e)) => {
-            stdout = None;
-            stderr = Some(format!("Failed to execute command: {}", e));
-            exit_code = Some(-1);
-            status = CommandStatus::Failed;
-            
-            error!("Command execution error: {}", e);
-        }
-        Err(_) => {
-            stdout = None;
-            stderr = Some(format!("Command timed out after {} seconds", timeout));
-            exit_code = Some(-1);
-            status = CommandStatus::TimedOut;
-            
-            warn!("Command timed out after {} seconds", timeout);
-        }
+    match status {
+        CommandStatus::TimedOut => warn!("Command timed out after {} seconds", timeout),
+        CommandStatus::Cancelled => warn!("Command was cancelled after {:?}", execution_time),
+        _ => info!(
+            "Command completed with exit code {:?} in {:?}",
+            exit_code, execution_time
+        ),
     }
-    
+
     // Update command result
-    {
-        let mut results = state.command_results.lock().unwrap();
-        if let Some(result) = results.get_mut(&id) {
-            result.status = status;
-            result.stdout = stdout;
-            result.stderr = stderr;
-            result.exit_code = exit_code;
-            result.execution_time_ms = Some(execution_time.as_millis() as u64);
-            result.completed_at = Some(Utc::now());
-        }
-    }
+    update_command_result(&state, &id, |result| {
+        result.status = status;
+        result.stdout = stdout;
+        result.stderr = stderr;
+        result.exit_code = exit_code;
+        result.execution_time_ms = Some(execution_time.as_millis() as u64);
+        result.completed_at = Some(Utc::now());
+    })
+    .await;
+
+    // The command finished on its own; its cancel signal is no longer actionable.
+    state.cancel_signals.lock().unwrap().remove(&id);
 }
 
 // Background task for VM command execution
@@ -395,165 +1377,198 @@ async fn execute_command_on_vm_task(
     state: Arc<AppState>,
     id: String,
     request: CommandRequest,
+    mut cancel_rx: oneshot::Receiver<()>,
 ) {
     let timeout = request.timeout_seconds.unwrap_or(60);
     let start_time = Instant::now();
     
     // Update status to running
-    {
-        let mut results = state.command_results.lock().unwrap();
-        if let Some(result) = results.get_mut(&id) {
-            result.status = CommandStatus::Running;
-        }
-    }
-    
+    update_command_result(&state, &id, |result| {
+        result.status = CommandStatus::Running;
+    })
+    .await;
+
     // Get VM details from VM Manager
     let vm_details = match get_vm_details(&state, &request).await {
         Ok(details) => details,
         Err(e) => {
             error!("Failed to get VM details: {}", e);
-            
-            // Update command result with error
-            {
-                let mut results = state.command_results.lock().unwrap();
-                if let Some(result) = results.get_mut(&id) {
-                    result.status = CommandStatus::Failed;
-                    result.stderr = Some(format!("Failed to get VM details: {}", e));
-                    result.exit_code = Some(-1);
-                    result.execution_time_ms = Some(start_time.elapsed().as_millis() as u64);
-                    result.completed_at = Some(Utc::now());
-                }
-            }
-            
+
+            let elapsed_ms = start_time.elapsed().as_millis() as u64;
+            update_command_result(&state, &id, move |result| {
+                result.status = CommandStatus::Failed;
+                result.stderr = Some(format!("Failed to get VM details: {}", e));
+                result.exit_code = Some(-1);
+                result.execution_time_ms = Some(elapsed_ms);
+                result.completed_at = Some(Utc::now());
+            })
+            .await;
+
+            state.cancel_signals.lock().unwrap().remove(&id);
             return;
         }
     };
-    
+
     // Update VM details in command result
-    {
-        let mut results = state.command_results.lock().unwrap();
-        if let Some(result) = results.get_mut(&id) {
+    update_command_result(&state, &id, {
+        let vm_details = vm_details.clone();
+        move |result| {
             result.vm_id = Some(vm_details.id.clone());
-            result.vm_details = Some(vm_details.clone());
+            result.vm_details = Some(vm_details);
         }
-    }
-    
+    })
+    .await;
+
     // Check if VM is running
     if vm_details.state.to_lowercase() != "running" {
         error!("VM is not running: {}", vm_details.state);
-        
-        // Update command result with error
-        {
-            let mut results = state.command_results.lock().unwrap();
-            if let Some(result) = results.get_mut(&id) {
-                result.status = CommandStatus::Failed;
-                result.stderr = Some(format!("VM is not running: {}", vm_details.state));
-                result.exit_code = Some(-1);
-                result.execution_time_ms = Some(start_time.elapsed().as_millis() as u64);
-                result.completed_at = Some(Utc::now());
-            }
-        }
-        
+
+        let elapsed_ms = start_time.elapsed().as_millis() as u64;
+        update_command_result(&state, &id, move |result| {
+            result.status = CommandStatus::Failed;
+            result.stderr = Some(format!("VM is not running: {}", vm_details.state));
+            result.exit_code = Some(-1);
+            result.execution_time_ms = Some(elapsed_ms);
+            result.completed_at = Some(Utc::now());
+        })
+        .await;
+
+        state.cancel_signals.lock().unwrap().remove(&id);
         return;
     }
-    
+
+    // If this VM has a polling agent registered, route the command through its pull-based
+    // queue instead of dialing SSH. This works behind NAT and needs no per-VM ngrok tunnel or
+    // SSH credentials; the agent executes the command locally and posts the result back via
+    // `POST /agent/result/:id`, which finishes updating the command record.
+    {
+        let mut agents = state.agents.lock().unwrap();
+        if let Some(agent) = agents.get_mut(&vm_details.id) {
+            agent.queue.push_back(AgentWorkItem {
+                id: id.clone(),
+                command: request.command.clone(),
+                working_directory: request.working_directory.clone(),
+                environment: request.environment.clone(),
+            });
+            agent.notify.notify_one();
+            state.agent_routed_commands.lock().unwrap().insert(id.clone());
+            info!("Routed command {} to polling agent for VM {}", id, vm_details.id);
+
+            // The agent has no channel to receive a cancellation (see the 409 `cancel_command`
+            // returns for a routed command) and may never poll again, so without this the
+            // command would sit at `Running` forever if no result ever arrives. Mirrors the
+            // same timeout applied to the SSH path below.
+            let watcher_state = state.clone();
+            let watcher_id = id.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_secs(timeout)).await;
+                let still_routed = watcher_state.agent_routed_commands.lock().unwrap().remove(&watcher_id);
+                if still_routed {
+                    warn!(
+                        "Command {} routed to a polling agent timed out after {} seconds with no result",
+                        watcher_id, timeout
+                    );
+                    update_command_result(&watcher_state, &watcher_id, |result| {
+                        result.status = CommandStatus::TimedOut;
+                        result.exit_code = Some(-1);
+                        result.completed_at = Some(Utc::now());
+                    })
+                    .await;
+                }
+            });
+            return;
+        }
+    }
+
     // Check if ngrok URL is available
     if vm_details.ngrok_url.is_none() {
         error!("VM does not have an ngrok URL");
-        
-        // Update command result with error
-        {
-            let mut results = state.command_results.lock().unwrap();
-            if let Some(result) = results.get_mut(&id) {
-                result.status = CommandStatus::Failed;
-                result.stderr = Some("VM does not have an ngrok URL".to_string());
-                result.exit_code = Some(-1);
-                result.execution_time_ms = Some(start_time.elapsed().as_millis() as u64);
-                result.completed_at = Some(Utc::now());
-            }
-        }
-        
+
+        let elapsed_ms = start_time.elapsed().as_millis() as u64;
+        update_command_result(&state, &id, move |result| {
+            result.status = CommandStatus::Failed;
+            result.stderr = Some("VM does not have an ngrok URL".to_string());
+            result.exit_code = Some(-1);
+            result.execution_time_ms = Some(elapsed_ms);
+            result.completed_at = Some(Utc::now());
+        })
+        .await;
+
+        state.cancel_signals.lock().unwrap().remove(&id);
         return;
     }
     
-    // Execute command on VM via SSH over ngrok
+    // Execute command on VM over a native SSH session
     info!("Executing command on VM {}: {}", vm_details.id, request.command);
-    
-    // Build SSH command
-    let ssh_command = format!(
-        "sshpass -p '{}' ssh -o StrictHostKeyChecking=no -o UserKnownHostsFile=/dev/null {} {}",
-        vm_details.ssh_password.as_ref().unwrap_or(&"".to_string()),
-        vm_details.ssh_username.as_ref().unwrap_or(&"agent".to_string()),
-        vm_details.ngrok_url.as_ref().unwrap()
-    );
-    
-    // Build the final command
-    let command = format!("{} '{}'", ssh_command, request.command);
-    
-    // Execute SSH command
-    let mut cmd = Command::new("sh");
-    cmd.arg("-c").arg(&command);
-    
-    // Configure stdio
-    cmd.stdout(Stdio::piped());
-    cmd.stderr(Stdio::piped());
-    
-    // Execute command with timeout
-    let result = tokio::time::timeout(Duration::from_secs(timeout), cmd.spawn().unwrap().wait_with_output()).await;
-    
-    // Process result
-    let execution_time = start_time.elapsed();
-    let status;
-    let stdout;
-    let stderr;
-    let exit_code;
-    
-    match result {
-        Ok(Ok(output)) => {
-            stdout = Some(String::from_utf8_lossy(&output.stdout).to_string());
-            stderr = Some(String::from_utf8_lossy(&output.stderr).to_string());
-            exit_code = output.status.code();
-            status = if output.status.success() {
-                CommandStatus::Completed
-            } else {
-                CommandStatus::Failed
-            };
-            
-            info!(
-                "VM command completed with exit code {:?} in {:?}",
-                exit_code, execution_time
-            );
-        }
-        Ok(Err(e)) => {
-            stdout = None;
-            stderr = Some(format!("Failed to execute command on VM: {}", e));
-            exit_code = Some(-1);
-            status = CommandStatus::Failed;
-            
-            error!("VM command execution error: {}", e);
+
+    let session = match get_or_create_ssh_session(&state, &vm_details).await {
+        Ok(session) => session,
+        Err(SshConnectError::Unreachable(e)) => {
+            error!("VM {} unreachable over SSH: {}", vm_details.id, e);
+
+            let elapsed_ms = start_time.elapsed().as_millis() as u64;
+            update_command_result(&state, &id, move |result| {
+                result.status = CommandStatus::SshUnreachable;
+                result.stderr = Some(format!("VM unreachable over SSH: {}", e));
+                result.exit_code = Some(-1);
+                result.execution_time_ms = Some(elapsed_ms);
+                result.completed_at = Some(Utc::now());
+            })
+            .await;
+            state.cancel_signals.lock().unwrap().remove(&id);
+            return;
         }
-        Err(_) => {
-            stdout = None;
-            stderr = Some(format!("Command on VM timed out after {} seconds", timeout));
-            exit_code = Some(-1);
-            status = CommandStatus::TimedOut;
-            
-            warn!("VM command timed out after {} seconds", timeout);
+        Err(SshConnectError::AuthFailed(e)) => {
+            error!("SSH authentication failed for VM {}: {}", vm_details.id, e);
+
+            let elapsed_ms = start_time.elapsed().as_millis() as u64;
+            update_command_result(&state, &id, move |result| {
+                result.status = CommandStatus::SshAuthFailed;
+                result.stderr = Some(format!("SSH authentication failed: {}", e));
+                result.exit_code = Some(-1);
+                result.execution_time_ms = Some(elapsed_ms);
+                result.completed_at = Some(Utc::now());
+            })
+            .await;
+            state.cancel_signals.lock().unwrap().remove(&id);
+            return;
         }
+    };
+
+    let (status, stdout, stderr, exit_code) =
+        match run_ssh_command(&state, &id, &session, &request.command, timeout, &mut cancel_rx).await {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                error!("VM command execution error: {}", e);
+                // A dead session can't be reused; drop it so the next command reconnects.
+                state.ssh_sessions.lock().unwrap().remove(&vm_details.id);
+                (CommandStatus::Failed, None, Some(format!("Failed to execute command on VM: {}", e)), Some(-1))
+            }
+        };
+
+    let execution_time = start_time.elapsed();
+    match status {
+        CommandStatus::TimedOut => warn!("Command on VM timed out after {} seconds", timeout),
+        CommandStatus::Cancelled => warn!("Command on VM was cancelled after {:?}", execution_time),
+        _ => info!(
+            "VM command completed with exit code {:?} in {:?}",
+            exit_code, execution_time
+        ),
     }
-    
+
     // Update command result
-    {
-        let mut results = state.command_results.lock().unwrap();
-        if let Some(result) = results.get_mut(&id) {
-            result.status = status;
-            result.stdout = stdout;
-            result.stderr = stderr;
-            result.exit_code = exit_code;
-            result.execution_time_ms = Some(execution_time.as_millis() as u64);
-            result.completed_at = Some(Utc::now());
-        }
-    }
+    update_command_result(&state, &id, |result| {
+        result.status = status;
+        result.stdout = stdout;
+        result.stderr = stderr;
+        result.exit_code = exit_code;
+        result.execution_time_ms = Some(execution_time.as_millis() as u64);
+        result.completed_at = Some(Utc::now());
+    })
+    .await;
+
+    // The command finished on its own; its cancel signal is no longer actionable.
+    state.cancel_signals.lock().unwrap().remove(&id);
 }
 
 // Helper function to get VM details
@@ -594,3 +1609,375 @@ async fn get_vm_details(state: &Arc<AppState>, request: &CommandRequest) -> Resu
         Err(e) => Err(format!("Failed to connect to VM Manager: {}", e)),
     }
 }
+
+// Splits an ngrok `tcp://host:port` tunnel URL into its host and port parts.
+fn parse_ngrok_addr(ngrok_url: &str) -> Result<(String, u16), String> {
+    let without_scheme = ngrok_url.trim_start_matches("tcp://");
+    let (host, port) = without_scheme
+        .rsplit_once(':')
+        .ok_or_else(|| format!("Invalid ngrok URL: {}", ngrok_url))?;
+    let port: u16 = port
+        .parse()
+        .map_err(|_| format!("Invalid ngrok URL port: {}", ngrok_url))?;
+    Ok((host.to_string(), port))
+}
+
+// Returns the live SSH session for `vm_details`, reusing one from `state.ssh_sessions` if a
+// prior command already opened it, or authenticating a fresh one otherwise.
+async fn get_or_create_ssh_session(
+    state: &Arc<AppState>,
+    vm_details: &VmDetails,
+) -> Result<Arc<Handle<SshClientHandler>>, SshConnectError> {
+    if let Some(session) = state.ssh_sessions.lock().unwrap().get(&vm_details.id) {
+        return Ok(session.clone());
+    }
+
+    let ngrok_url = vm_details
+        .ngrok_url
+        .as_ref()
+        .ok_or_else(|| SshConnectError::Unreachable("VM does not have an ngrok URL".to_string()))?;
+    let (host, port) =
+        parse_ngrok_addr(ngrok_url).map_err(SshConnectError::Unreachable)?;
+
+    let config = Arc::new(client::Config::default());
+    let mut handle = client::connect(config, (host.as_str(), port), SshClientHandler)
+        .await
+        .map_err(|e| SshConnectError::Unreachable(e.to_string()))?;
+
+    let username = vm_details.ssh_username.as_deref().unwrap_or("agent");
+    let password = vm_details.ssh_password.as_deref().unwrap_or("");
+    let authenticated = handle
+        .authenticate_password(username, password)
+        .await
+        .map_err(|e| SshConnectError::AuthFailed(e.to_string()))?;
+    if !authenticated {
+        return Err(SshConnectError::AuthFailed(
+            "VM rejected the supplied SSH credentials".to_string(),
+        ));
+    }
+
+    let session = Arc::new(handle);
+    state
+        .ssh_sessions
+        .lock()
+        .unwrap()
+        .insert(vm_details.id.clone(), session.clone());
+    Ok(session)
+}
+
+// Opens a fresh SFTP subsystem channel over the VM's SSH session. Unlike the SSH session itself,
+// this channel isn't cached in `AppState`: file transfers are comparatively rare and short-lived, so a new
+// channel per request keeps the session reuse logic above simple.
+async fn open_sftp_session(session: &Handle<SshClientHandler>) -> Result<SftpSession, String> {
+    let mut channel = session
+        .channel_open_session()
+        .await
+        .map_err(|e| format!("Failed to open SSH channel: {}", e))?;
+    channel
+        .request_subsystem(true, "sftp")
+        .await
+        .map_err(|e| format!("Failed to start SFTP subsystem: {}", e))?;
+    SftpSession::new(channel.into_stream())
+        .await
+        .map_err(|e| format!("Failed to start SFTP session: {}", e))
+}
+
+// Resolves the VM a `/files/*` request targets, mirroring the task_id/vm_id lookup command
+// execution uses.
+async fn resolve_vm_for_files(
+    state: &Arc<AppState>,
+    vm_id: Option<String>,
+    task_id: Option<String>,
+) -> Result<VmDetails, String> {
+    get_vm_details(
+        state,
+        &CommandRequest {
+            command: String::new(),
+            task_id,
+            vm_id,
+            timeout_seconds: None,
+            working_directory: None,
+            environment: None,
+        },
+    )
+    .await
+}
+
+// Runs `command` over an SSH exec channel on `session`, streaming stdout/stderr chunks to the
+// command's broadcast channel exactly like a local execution, and enforcing `timeout_secs`.
+async fn run_ssh_command(
+    state: &Arc<AppState>,
+    id: &str,
+    session: &Handle<SshClientHandler>,
+    command: &str,
+    timeout_secs: u64,
+    cancel_rx: &mut oneshot::Receiver<()>,
+) -> Result<(CommandStatus, Option<String>, Option<String>, Option<i32>), String> {
+    let mut channel = session
+        .channel_open_session()
+        .await
+        .map_err(|e| format!("Failed to open SSH channel: {}", e))?;
+    channel
+        .exec(true, command)
+        .await
+        .map_err(|e| format!("Failed to exec over SSH: {}", e))?;
+
+    let mut stdout = String::new();
+    let mut stderr = String::new();
+    let mut exit_code = None;
+
+    let read_loop = async {
+        while let Some(msg) = channel.wait().await {
+            match msg {
+                ChannelMsg::Data { data } => {
+                    let text = String::from_utf8_lossy(&data).to_string();
+                    stdout.push_str(&text);
+                    broadcast_chunk(state, id, "stdout", text);
+                }
+                ChannelMsg::ExtendedData { data, ext: 1 } => {
+                    let text = String::from_utf8_lossy(&data).to_string();
+                    stderr.push_str(&text);
+                    broadcast_chunk(state, id, "stderr", text);
+                }
+                ChannelMsg::ExitStatus { exit_status } => {
+                    exit_code = Some(exit_status as i32);
+                }
+                _ => {}
+            }
+        }
+    };
+
+    let status = tokio::select! {
+        result = tokio::time::timeout(Duration::from_secs(timeout_secs), read_loop) => {
+            match result {
+                Ok(()) => match exit_code {
+                    Some(0) => CommandStatus::Completed,
+                    _ => CommandStatus::Failed,
+                },
+                Err(_) => {
+                    let _ = channel.close().await;
+                    exit_code = Some(-1);
+                    CommandStatus::TimedOut
+                }
+            }
+        }
+        _ = &mut *cancel_rx => {
+            // Send EOF before closing so the remote shell gets a clean signal to stop.
+            let _ = channel.eof().await;
+            let _ = channel.close().await;
+            exit_code = Some(-1);
+            CommandStatus::Cancelled
+        }
+    };
+
+    Ok((status, Some(stdout), Some(stderr), exit_code))
+}
+
+// Publishes a chunk of output to a command's broadcast channel, if anyone is subscribed.
+fn broadcast_chunk(state: &Arc<AppState>, id: &str, stream_name: &str, data: String) {
+    if let Some(tx) = state.output_channels.lock().unwrap().get(id) {
+        let _ = tx.send(OutputChunk {
+            stream: stream_name.to_string(),
+            data,
+        });
+    }
+}
+
+// Spawns `cmd` and streams its stdout/stderr to the command's broadcast channel as they are
+// produced, while also accumulating them so the full output is still available once the
+// command finishes. Enforces `timeout_secs`, killing the child and reporting `TimedOut` if it
+// is exceeded.
+async fn run_and_stream_command(
+    state: &Arc<AppState>,
+    id: &str,
+    mut cmd: Command,
+    timeout_secs: u64,
+    mut cancel_rx: oneshot::Receiver<()>,
+) -> Result<(CommandStatus, Option<String>, Option<String>, Option<i32>), String> {
+    cmd.stdout(Stdio::piped());
+    cmd.stderr(Stdio::piped());
+
+    let mut child = cmd.spawn().map_err(|e| format!("Failed to spawn command: {}", e))?;
+    let stdout_pipe = child.stdout.take().expect("stdout was piped");
+    let stderr_pipe = child.stderr.take().expect("stderr was piped");
+
+    let stdout_buf = Arc::new(Mutex::new(String::new()));
+    let stderr_buf = Arc::new(Mutex::new(String::new()));
+
+    let stdout_task = tokio::spawn(pump_output(
+        state.clone(),
+        id.to_string(),
+        "stdout".to_string(),
+        stdout_pipe,
+        stdout_buf.clone(),
+    ));
+    let stderr_task = tokio::spawn(pump_output(
+        state.clone(),
+        id.to_string(),
+        "stderr".to_string(),
+        stderr_pipe,
+        stderr_buf.clone(),
+    ));
+
+    let (status, exit_code, wait_error) = tokio::select! {
+        result = tokio::time::timeout(Duration::from_secs(timeout_secs), child.wait()) => {
+            match result {
+                Ok(Ok(exit_status)) => {
+                    let status = if exit_status.success() {
+                        CommandStatus::Completed
+                    } else {
+                        CommandStatus::Failed
+                    };
+                    (status, exit_status.code(), None)
+                }
+                Ok(Err(e)) => (CommandStatus::Failed, Some(-1), Some(e.to_string())),
+                Err(_) => {
+                    let _ = child.kill().await;
+                    (CommandStatus::TimedOut, Some(-1), None)
+                }
+            }
+        }
+        _ = &mut cancel_rx => {
+            let _ = child.kill().await;
+            (CommandStatus::Cancelled, Some(-1), None)
+        }
+    };
+
+    // The pipes close once the child exits (or is killed), letting the pump tasks drain
+    // whatever is left and return.
+    let _ = stdout_task.await;
+    let _ = stderr_task.await;
+
+    let stdout = Some(stdout_buf.lock().unwrap().clone());
+    let stderr = match wait_error {
+        Some(e) => Some(format!("Failed to wait for command: {}", e)),
+        None => Some(stderr_buf.lock().unwrap().clone()),
+    };
+
+    Ok((status, stdout, stderr, exit_code))
+}
+
+// Reads `pipe` in bounded chunks, forwarding each chunk to the command's broadcast channel (if
+// any subscribers are listening) and appending it to `buf` so `get_command_result` has the full
+// output once the command completes. Output is not stored while the command runs, so a stream
+// subscriber only receives the chunks sent after it subscribed.
+async fn pump_output<R: tokio::io::AsyncRead + Unpin>(
+    state: Arc<AppState>,
+    id: String,
+    stream_name: String,
+    mut pipe: R,
+    buf: Arc<Mutex<String>>,
+) {
+    let mut chunk = vec![0u8; OUTPUT_CHUNK_SIZE];
+    loop {
+        match pipe.read(&mut chunk).await {
+            Ok(0) => break,
+            Ok(n) => {
+                let text = String::from_utf8_lossy(&chunk[..n]).to_string();
+                buf.lock().unwrap().push_str(&text);
+
+                if let Some(tx) = state.output_channels.lock().unwrap().get(&id) {
+                    let _ = tx.send(OutputChunk {
+                        stream: stream_name.clone(),
+                        data: text,
+                    });
+                }
+            }
+            Err(e) => {
+                warn!("Error reading {} for command {}: {}", stream_name, id, e);
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_command_result(id: &str) -> CommandResult {
+        CommandResult {
+            id: id.to_string(),
+            task_id: Some("task-1".to_string()),
+            vm_id: None,
+            command: "echo hi".to_string(),
+            status: CommandStatus::Pending,
+            stdout: None,
+            stderr: None,
+            exit_code: None,
+            execution_time_ms: None,
+            created_at: Utc::now(),
+            completed_at: None,
+            vm_details: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn insert_then_get_round_trips_a_command_result() {
+        let db = DbCtx::new(":memory:").expect("open in-memory db");
+        let inserted = sample_command_result("cmd-test-1");
+        db.insert_command(&inserted).await.expect("insert");
+
+        let fetched = db
+            .get_command(&inserted.id)
+            .await
+            .expect("get")
+            .expect("row exists");
+
+        assert_eq!(fetched.id, inserted.id);
+        assert_eq!(fetched.task_id, inserted.task_id);
+        assert_eq!(fetched.command, inserted.command);
+        assert_eq!(fetched.status, CommandStatus::Pending);
+    }
+
+    #[tokio::test]
+    async fn update_command_persists_a_terminal_status() {
+        let db = DbCtx::new(":memory:").expect("open in-memory db");
+        let mut result = sample_command_result("cmd-test-2");
+        db.insert_command(&result).await.expect("insert");
+
+        result.status = CommandStatus::Completed;
+        result.stdout = Some("hi\n".to_string());
+        result.exit_code = Some(0);
+        result.completed_at = Some(Utc::now());
+        db.update_command(&result).await.expect("update");
+
+        let fetched = db
+            .get_command(&result.id)
+            .await
+            .expect("get")
+            .expect("row exists");
+
+        assert_eq!(fetched.status, CommandStatus::Completed);
+        assert_eq!(fetched.stdout, Some("hi\n".to_string()));
+        assert_eq!(fetched.exit_code, Some(0));
+    }
+
+    #[tokio::test]
+    async fn get_command_returns_none_for_unknown_id() {
+        let db = DbCtx::new(":memory:").expect("open in-memory db");
+        assert!(db.get_command("does-not-exist").await.expect("get").is_none());
+    }
+
+    #[test]
+    fn resolve_existing_local_path_rejects_escape_via_dotdot() {
+        let root = std::fs::canonicalize(std::env::temp_dir()).expect("canonicalize temp dir");
+        assert!(resolve_existing_local_path(&root, "../etc/passwd").is_err());
+    }
+
+    #[test]
+    fn resolve_existing_local_path_rejects_absolute_path() {
+        let root = std::fs::canonicalize(std::env::temp_dir()).expect("canonicalize temp dir");
+        assert!(resolve_existing_local_path(&root, "/etc/passwd").is_err());
+    }
+
+    #[test]
+    fn resolve_new_local_path_confines_write_target_to_root() {
+        let root = std::fs::canonicalize(std::env::temp_dir()).expect("canonicalize temp dir");
+
+        let resolved = resolve_new_local_path(&root, "staged.txt").expect("path within root");
+        assert_eq!(resolved, root.join("staged.txt"));
+
+        assert!(resolve_new_local_path(&root, "../outside.txt").is_err());
+    }
+}
@@ -1,8 +1,31 @@
-use std::process::Command;
+use std::cell::RefCell;
+use std::process::{Child, Command, Stdio};
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use glob::glob;
+use hmac::{Hmac, Mac};
+use mlua::{HookTriggers, Lua, Table};
+use portable_pty::{native_pty_system, CommandBuilder, MasterPty, PtySize};
 use serde::{Deserialize, Serialize};
-use tiny_http::{Server, Response, Method, Header};
+use sha2::Sha256;
+use tiny_http::{Server, Response, Method, Header, StatusCode};
+
+// Root directory under which each job gets its own artifact subdirectory (named after its job
+// id), holding its captured stdout/stderr and any files matched by `artifact_paths`.
+const ARTIFACTS_ROOT: &str = "job-artifacts";
+
+type HmacSha256 = Hmac<Sha256>;
+
+// Header names carrying the caller's identity and its HMAC-SHA256(psk, raw_body) signature.
+const CLIENT_ID_HEADER: &str = "X-Client-Id";
+const SIGNATURE_HEADER: &str = "X-Signature";
 
 // Command request structure
 #[derive(Deserialize)]
@@ -10,6 +33,329 @@ struct CommandRequest {
     command: String,
     args: Option<Vec<String>>,
     working_dir: Option<String>,
+    // Kills the process and records `status:"timeout"` if it's still running after this long.
+    timeout_ms: Option<u64>,
+    // `POST /sessions` always allocates a pty for the child and rejects an explicit `false`;
+    // `/execute` and `/execute/stream` ignore this flag and use piped stdout/stderr.
+    pty: Option<bool>,
+    cols: Option<u16>,
+    rows: Option<u16>,
+    // If set, the final `CommandResponse` is POSTed here once the job reaches a terminal status
+    // (success, failed, timeout, or cancelled), so callers don't have to poll `/results/{id}`.
+    callback_url: Option<String>,
+    // Globs, relative to `working_dir`, collected into the job's artifact directory after the
+    // process exits and served via `GET /results/{id}/artifacts/{name}`.
+    artifact_paths: Option<Vec<String>>,
+}
+
+// Number of worker threads pulling queued commands off the job queue.
+const EXECUTE_WORKER_THREADS: usize = 4;
+
+// How a running command finished.
+enum JobOutcome {
+    Completed(std::process::ExitStatus),
+    TimedOut,
+    Cancelled,
+}
+
+// A command the worker pool is currently executing, tracked so `DELETE /jobs/:id` can find and
+// kill it and so the worker loop can tell a cancellation apart from a timeout.
+struct RunningJob {
+    child: Arc<Mutex<Child>>,
+    cancelled: Arc<AtomicBool>,
+}
+
+// A live pty-backed interactive session created by `POST /sessions`. `writer` forwards stdin
+// bytes into the pty master; `master` is kept alive so `/sessions/:id/stdin` can resize it.
+struct PtySession {
+    writer: Arc<Mutex<Box<dyn Write + Send>>>,
+    master: Arc<Mutex<Box<dyn MasterPty + Send>>>,
+}
+
+// One control frame sent to `POST /sessions/:id/stdin`: either base64-encoded bytes to forward
+// to the pty, or an in-band resize request.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StdinFrame {
+    Resize { resize: ResizeRequest },
+    Data { data: String },
+}
+
+#[derive(Deserialize)]
+struct ResizeRequest {
+    cols: u16,
+    rows: u16,
+}
+
+// Request body for `/run-script`: a Lua recipe plus the default working directory its `run()`
+// calls use unless overridden by `cwd()` or a per-call `cwd` option.
+#[derive(Deserialize)]
+struct RunScriptRequest {
+    script: String,
+    working_dir: Option<String>,
+    // Kills whichever command the recipe is currently running, and aborts the rest of the
+    // script, if the job is still going after this long.
+    timeout_ms: Option<u64>,
+}
+
+// One `run()` call made by a Lua recipe, captured as its own step so `/results/{id}` can return
+// the ordered sequence of commands a script executed.
+#[derive(Serialize, Clone)]
+struct ScriptStepResult {
+    command: String,
+    status: String,
+    stdout: String,
+    stderr: String,
+    exit_code: i32,
+}
+
+// The aggregate result of a `/run-script` job: every step the recipe ran, in order, plus an
+// overall status that's "failed" if the script raised an error (including via a `run()` call
+// that opted into `fail_on_error`), or "timeout"/"cancelled" if the job's deadline or a
+// cancellation aborted it.
+#[derive(Serialize, Clone)]
+struct ScriptJobResponse {
+    id: String,
+    status: String,
+    steps: Vec<ScriptStepResult>,
+    client_id: Option<String>,
+}
+
+// Number of worker threads executing queued `/run-script` recipes.
+const SCRIPT_WORKER_THREADS: usize = 2;
+
+// Runs a Lua recipe against an embedded interpreter, exposing `run(cmd, opts)` and `cwd(path)` as
+// its host API. `opts` is an optional table with `args`, `cwd`, `env`, and `fail_on_error` keys;
+// `run()` returns `{exit_code, stdout, stderr}` and raises a Lua error (aborting the recipe) when
+// `fail_on_error` is true and the command exits nonzero. Returns the recipe's overall status and
+// the ordered list of steps it ran, regardless of whether it completed or aborted partway through.
+//
+// `run()` spawns each command the same way the `/execute` worker pool does, registering it in
+// `running_jobs` under `job_id` so `DELETE /jobs/:id` can kill whichever command the recipe is
+// currently running, and polling against `deadline` so a hung command can't pin a script worker
+// thread forever. Either one aborts the rest of the script.
+fn run_lua_script(
+    job_id: &str,
+    script: &str,
+    default_cwd: Option<String>,
+    deadline: Option<Instant>,
+    cancelled: Arc<AtomicBool>,
+    running_jobs: Arc<Mutex<HashMap<String, RunningJob>>>,
+) -> (String, Vec<ScriptStepResult>) {
+    let lua = Lua::new();
+    let steps: Rc<RefCell<Vec<ScriptStepResult>>> = Rc::new(RefCell::new(Vec::new()));
+    let cwd_state: Rc<RefCell<Option<String>>> = Rc::new(RefCell::new(default_cwd));
+    // Set when a step is killed by `deadline` or `cancelled`, overriding the overall status the
+    // Lua error that abort produces would otherwise be reported as ("failed").
+    let abort_reason: Rc<RefCell<Option<&'static str>>> = Rc::new(RefCell::new(None));
+
+    let run_steps = steps.clone();
+    let run_cwd = cwd_state.clone();
+    let run_abort_reason = abort_reason.clone();
+    let run_job_id = job_id.to_string();
+    let hook_cancelled = cancelled.clone();
+    let hook_abort_reason = abort_reason.clone();
+    let run_fn = lua
+        .create_function(move |lua_ctx, (cmd, opts): (String, Option<Table>)| {
+            let mut args: Vec<String> = Vec::new();
+            let mut cwd_override: Option<String> = None;
+            let mut env_vars: Vec<(String, String)> = Vec::new();
+            let mut fail_on_error = false;
+
+            if let Some(opts) = &opts {
+                if let Ok(a) = opts.get::<_, Vec<String>>("args") {
+                    args = a;
+                }
+                if let Ok(c) = opts.get::<_, String>("cwd") {
+                    cwd_override = Some(c);
+                }
+                if let Ok(env_table) = opts.get::<_, Table>("env") {
+                    for (key, value) in env_table.pairs::<String, String>().flatten() {
+                        env_vars.push((key, value));
+                    }
+                }
+                if let Ok(f) = opts.get::<_, bool>("fail_on_error") {
+                    fail_on_error = f;
+                }
+            }
+
+            if cancelled.load(Ordering::SeqCst) {
+                *run_abort_reason.borrow_mut() = Some("cancelled");
+                return Err(mlua::Error::RuntimeError("script job was cancelled".to_string()));
+            }
+
+            let mut command = Command::new(&cmd);
+            command.args(&args);
+            if let Some(dir) = cwd_override.or_else(|| run_cwd.borrow().clone()) {
+                command.current_dir(dir);
+            }
+            for (key, value) in &env_vars {
+                command.env(key, value);
+            }
+            command.stdout(Stdio::piped());
+            command.stderr(Stdio::piped());
+
+            let child = match command.spawn() {
+                Ok(child) => child,
+                Err(e) => {
+                    let stderr = format!("Failed to execute command: {}", e);
+                    run_steps.borrow_mut().push(ScriptStepResult {
+                        command: cmd.clone(),
+                        status: "failed".to_string(),
+                        stdout: String::new(),
+                        stderr: stderr.clone(),
+                        exit_code: -1,
+                    });
+                    if fail_on_error {
+                        return Err(mlua::Error::RuntimeError(stderr));
+                    }
+                    let result = lua_ctx.create_table()?;
+                    result.set("exit_code", -1)?;
+                    result.set("stdout", "")?;
+                    result.set("stderr", stderr)?;
+                    return Ok(result);
+                }
+            };
+
+            let child = Arc::new(Mutex::new(child));
+            running_jobs.lock().unwrap().insert(
+                run_job_id.clone(),
+                RunningJob { child: child.clone(), cancelled: cancelled.clone() },
+            );
+
+            let outcome = loop {
+                if let Ok(Some(exit_status)) = child.lock().unwrap().try_wait() {
+                    if cancelled.load(Ordering::SeqCst) {
+                        break JobOutcome::Cancelled;
+                    }
+                    break JobOutcome::Completed(exit_status);
+                }
+                if let Some(deadline) = deadline {
+                    if Instant::now() >= deadline {
+                        let mut child = child.lock().unwrap();
+                        let _ = child.kill();
+                        let _ = child.wait();
+                        break JobOutcome::TimedOut;
+                    }
+                }
+                thread::sleep(Duration::from_millis(25));
+            };
+            running_jobs.lock().unwrap().remove(&run_job_id);
+
+            let mut child = child.lock().unwrap();
+            let mut stdout = String::new();
+            let mut stderr = String::new();
+            if let Some(mut pipe) = child.stdout.take() {
+                let _ = pipe.read_to_string(&mut stdout);
+            }
+            if let Some(mut pipe) = child.stderr.take() {
+                let _ = pipe.read_to_string(&mut stderr);
+            }
+
+            let (status, exit_code) = match outcome {
+                JobOutcome::Completed(exit_status) => {
+                    let exit_code = exit_status.code().unwrap_or(-1);
+                    (if exit_status.success() { "success" } else { "failed" }.to_string(), exit_code)
+                }
+                JobOutcome::TimedOut => {
+                    *run_abort_reason.borrow_mut() = Some("timeout");
+                    ("timeout".to_string(), -1)
+                }
+                JobOutcome::Cancelled => {
+                    *run_abort_reason.borrow_mut() = Some("cancelled");
+                    ("cancelled".to_string(), -1)
+                }
+            };
+
+            run_steps.borrow_mut().push(ScriptStepResult {
+                command: cmd.clone(),
+                status: status.clone(),
+                stdout: stdout.clone(),
+                stderr: stderr.clone(),
+                exit_code,
+            });
+
+            if matches!(outcome, JobOutcome::TimedOut | JobOutcome::Cancelled) {
+                return Err(mlua::Error::RuntimeError(format!("command `{}` was {}", cmd, status)));
+            }
+            if fail_on_error && exit_code != 0 {
+                return Err(mlua::Error::RuntimeError(format!(
+                    "command `{}` exited with code {}",
+                    cmd, exit_code
+                )));
+            }
+
+            let result = lua_ctx.create_table()?;
+            result.set("exit_code", exit_code)?;
+            result.set("stdout", stdout)?;
+            result.set("stderr", stderr)?;
+            Ok(result)
+        })
+        .unwrap();
+    lua.globals().set("run", run_fn).unwrap();
+
+    let cwd_fn_state = cwd_state.clone();
+    let cwd_fn = lua
+        .create_function(move |_, path: String| {
+            *cwd_fn_state.borrow_mut() = Some(path);
+            Ok(())
+        })
+        .unwrap();
+    lua.globals().set("cwd", cwd_fn).unwrap();
+
+    // `run()` only gets a chance to notice `deadline`/`cancelled` while it is being called, so a
+    // script that never calls `run()` (e.g. a tight Lua-only loop) would otherwise run forever.
+    // This hook fires every 10,000 VM instructions regardless of what the script is doing and
+    // raises the same interrupting error `run()` does.
+    lua.set_hook(
+        HookTriggers::new().every_nth_instruction(10_000),
+        move |_lua, _debug| {
+            if hook_cancelled.load(Ordering::SeqCst) {
+                *hook_abort_reason.borrow_mut() = Some("cancelled");
+                return Err(mlua::Error::RuntimeError("script job was cancelled".to_string()));
+            }
+            if let Some(deadline) = deadline {
+                if Instant::now() >= deadline {
+                    *hook_abort_reason.borrow_mut() = Some("timeout");
+                    return Err(mlua::Error::RuntimeError("script job timed out".to_string()));
+                }
+            }
+            Ok(())
+        },
+    );
+
+    let overall_status = match lua.load(script).exec() {
+        Ok(()) => "success",
+        Err(_) => abort_reason.borrow().unwrap_or("failed"),
+    };
+
+    let recorded_steps = steps.borrow().clone();
+    (overall_status.to_string(), recorded_steps)
+}
+
+// Reads `pipe` in fixed-size chunks, writing everything it sees straight to a file at
+// `file_path`, until the pipe closes. Used to capture a command's output as it runs without
+// holding the whole thing in memory; a timeout or cancellation still leaves whatever was written
+// before the kill on disk.
+fn spawn_capturing_reader<R: Read + Send + 'static>(mut pipe: R, file_path: PathBuf) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let mut file = match std::fs::File::create(&file_path) {
+            Ok(file) => file,
+            Err(_) => return,
+        };
+        let mut chunk = [0u8; 8192];
+        loop {
+            match pipe.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(n) => {
+                    if file.write_all(&chunk[..n]).is_err() {
+                        break;
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+    })
 }
 
 // Command response structure
@@ -20,6 +366,289 @@ struct CommandResponse {
     stdout: String,
     stderr: String,
     exit_code: i32,
+    // Identity of the pre-shared key that authenticated the request which created this job, if
+    // any, so results can be audited back to a caller.
+    client_id: Option<String>,
+    // Files collected into this job's artifact directory: its captured stdout/stderr plus
+    // whatever `artifact_paths` matched, retrievable via `GET /results/{id}/artifacts/{name}`.
+    artifacts: Vec<ArtifactInfo>,
+}
+
+// Metadata for one file collected into a job's artifact directory.
+#[derive(Serialize, Clone)]
+struct ArtifactInfo {
+    name: String,
+    size: u64,
+    content_type: String,
+    // Path on disk, relative to the service's working directory (under `ARTIFACTS_ROOT`); not
+    // served directly, just used by the artifacts handler to find the file again.
+    path: String,
+}
+
+// The on-disk directory a job's artifacts (captured stdout/stderr, matched `artifact_paths`
+// files) are collected into.
+fn job_artifact_dir(cmd_id: &str) -> PathBuf {
+    Path::new(ARTIFACTS_ROOT).join(cmd_id)
+}
+
+// Builds an `ArtifactInfo` for a file already at `path`, named `name`. Returns `None` if the file
+// can't be stat'd (e.g. a command that never wrote to stdout/stderr).
+fn artifact_info_for(path: &Path, name: &str) -> Option<ArtifactInfo> {
+    let metadata = std::fs::metadata(path).ok()?;
+    if !metadata.is_file() {
+        return None;
+    }
+    Some(ArtifactInfo {
+        name: name.to_string(),
+        size: metadata.len(),
+        content_type: guess_content_type(path),
+        path: path.to_string_lossy().to_string(),
+    })
+}
+
+// Guesses a content type from a file's extension. Falls back to a generic binary type since the
+// artifact store has no other way to know what a command produced.
+fn guess_content_type(path: &Path) -> String {
+    let content_type = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("txt") | Some("log") => "text/plain",
+        Some("json") => "application/json",
+        Some("html") | Some("htm") => "text/html",
+        Some("xml") => "application/xml",
+        Some("csv") => "text/csv",
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("zip") => "application/zip",
+        Some("tar") => "application/x-tar",
+        Some("gz") => "application/gzip",
+        Some("pdf") => "application/pdf",
+        _ => "application/octet-stream",
+    };
+    content_type.to_string()
+}
+
+// Resolves each of `patterns` (globs relative to `working_dir`) to the files they match, copies
+// each one into `job_dir`, and returns their metadata. Files are flattened into `job_dir` by
+// their base name, so two different matched paths sharing a name will collide; last one copied
+// wins.
+fn collect_glob_artifacts(job_dir: &Path, working_dir: Option<&str>, patterns: Option<&[String]>) -> Vec<ArtifactInfo> {
+    let patterns = match patterns {
+        Some(patterns) if !patterns.is_empty() => patterns,
+        _ => return Vec::new(),
+    };
+    let base = working_dir.map(Path::new).unwrap_or_else(|| Path::new("."));
+    // Resolved once up front so every match can be checked against it; an `artifact_paths`
+    // pattern that's itself absolute (e.g. "/etc/passwd") would otherwise have `Path::join`
+    // silently discard `base` and escape `working_dir` entirely.
+    let base_canon = match std::fs::canonicalize(base) {
+        Ok(base_canon) => base_canon,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut artifacts = Vec::new();
+    for pattern in patterns {
+        let full_pattern = match base.join(pattern).to_str() {
+            Some(full_pattern) => full_pattern.to_string(),
+            None => continue,
+        };
+        let entries = match glob(&full_pattern) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for entry in entries.flatten() {
+            if !entry.is_file() {
+                continue;
+            }
+            let entry_canon = match std::fs::canonicalize(&entry) {
+                Ok(entry_canon) => entry_canon,
+                Err(_) => continue,
+            };
+            if !entry_canon.starts_with(&base_canon) {
+                continue;
+            }
+            let name = match entry.file_name().and_then(|n| n.to_str()) {
+                Some(name) => name.to_string(),
+                None => continue,
+            };
+            let dest = job_dir.join(&name);
+            if std::fs::copy(&entry_canon, &dest).is_err() {
+                continue;
+            }
+            if let Some(artifact) = artifact_info_for(&dest, &name) {
+                artifacts.push(artifact);
+            }
+        }
+    }
+    artifacts
+}
+
+// Parses a `Range: bytes=start-end` header into inclusive `(start, end)` byte offsets; `end` is
+// `None` for an open-ended range like `bytes=500-`.
+fn parse_range_header(value: &str) -> Option<(u64, Option<u64>)> {
+    let spec = value.strip_prefix("bytes=")?;
+    let (start, end) = spec.split_once('-')?;
+    let start: u64 = start.parse().ok()?;
+    let end = if end.is_empty() { None } else { Some(end.parse().ok()?) };
+    Some((start, end))
+}
+
+// Loads the pre-shared keys used to authenticate `/execute` requests from
+// `COMMAND_EXECUTOR_PSKS`, formatted as `client_id:key,client_id2:key2`.
+fn load_psks() -> HashMap<String, String> {
+    std::env::var("COMMAND_EXECUTOR_PSKS")
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|entry| entry.split_once(':'))
+        .map(|(id, key)| (id.trim().to_string(), key.trim().to_string()))
+        .filter(|(id, key)| !id.is_empty() && !key.is_empty())
+        .collect()
+}
+
+// Verifies that `signature_hex` is HMAC-SHA256(psk, body) for the pre-shared key registered to
+// `client_id`. `Mac::verify_slice` compares in constant time.
+fn verify_signature(psks: &HashMap<String, String>, client_id: &str, signature_hex: &str, body: &[u8]) -> bool {
+    let psk = match psks.get(client_id) {
+        Some(psk) => psk,
+        None => return false,
+    };
+    let signature = match hex::decode(signature_hex) {
+        Ok(signature) => signature,
+        Err(_) => return false,
+    };
+    let mut mac = match HmacSha256::new_from_slice(psk.as_bytes()) {
+        Ok(mac) => mac,
+        Err(_) => return false,
+    };
+    mac.update(body);
+    mac.verify_slice(&signature).is_ok()
+}
+
+// Reads the client-id/signature headers off `request` and verifies `body` against the
+// registered pre-shared key, returning the authenticated client id on success.
+fn authenticate(request: &tiny_http::Request, psks: &HashMap<String, String>, body: &[u8]) -> Option<String> {
+    let header_value = |name: &str| {
+        request
+            .headers()
+            .iter()
+            .find(|h| h.field.as_str().as_str().eq_ignore_ascii_case(name))
+            .map(|h| h.value.as_str().to_string())
+    };
+
+    let client_id = header_value(CLIENT_ID_HEADER)?;
+    let signature = header_value(SIGNATURE_HEADER)?;
+
+    if verify_signature(psks, &client_id, &signature, body) {
+        Some(client_id)
+    } else {
+        None
+    }
+}
+
+// Bounds on the completion-webhook notifier: how many times it will try to deliver a callback
+// and how long it waits between attempts, doubling after each failure.
+const WEBHOOK_MAX_ATTEMPTS: u32 = 5;
+const WEBHOOK_INITIAL_BACKOFF_MS: u64 = 500;
+
+// POSTs a job's final `CommandResponse` to `callback_url`, retrying with exponential backoff on a
+// non-2xx status or connection error. If the job's `client_id` has a registered pre-shared key,
+// the body is signed with the same HMAC-SHA256 scheme `/execute` uses to authenticate requests,
+// carried in the same `X-Client-Id`/`X-Signature` headers, so receivers can verify it.
+fn notify_webhook(callback_url: &str, response: &CommandResponse, psks: &HashMap<String, String>) {
+    let body = serde_json::to_vec(response).unwrap();
+
+    let signature = response.client_id.as_ref().and_then(|client_id| {
+        let psk = psks.get(client_id)?;
+        let mut mac = HmacSha256::new_from_slice(psk.as_bytes()).ok()?;
+        mac.update(&body);
+        Some(hex::encode(mac.finalize().into_bytes()))
+    });
+
+    let mut backoff = Duration::from_millis(WEBHOOK_INITIAL_BACKOFF_MS);
+    for attempt in 0..WEBHOOK_MAX_ATTEMPTS {
+        let mut req = ureq::post(callback_url).set("Content-Type", "application/json");
+        if let (Some(client_id), Some(signature)) = (&response.client_id, &signature) {
+            req = req.set(CLIENT_ID_HEADER, client_id).set(SIGNATURE_HEADER, signature);
+        }
+
+        match req.send_bytes(&body) {
+            Ok(_) => return,
+            Err(_) => {
+                if attempt + 1 == WEBHOOK_MAX_ATTEMPTS {
+                    return;
+                }
+                thread::sleep(backoff);
+                backoff *= 2;
+            }
+        }
+    }
+}
+
+// A single chunk of output emitted while a command runs under `/execute/stream`, or the final
+// event carrying its exit code.
+#[derive(Serialize)]
+#[serde(untagged)]
+enum StreamEvent {
+    Chunk { stream: &'static str, data: String },
+    Done { exit_code: i32 },
+}
+
+// Reads `pipe` in fixed-size chunks on its own thread, forwarding each chunk to `tx` as a
+// `StreamEvent` so stdout and stderr can be read concurrently without either one blocking the
+// other.
+fn spawn_pipe_reader<R: Read + Send + 'static>(
+    stream_name: &'static str,
+    mut pipe: R,
+    tx: mpsc::Sender<StreamEvent>,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let mut buf = [0u8; 4096];
+        loop {
+            match pipe.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => {
+                    let data = String::from_utf8_lossy(&buf[..n]).to_string();
+                    if tx.send(StreamEvent::Chunk { stream: stream_name, data }).is_err() {
+                        break;
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+    })
+}
+
+// Adapts the `StreamEvent` channel into a `Read` so it can be handed to tiny_http as a chunked
+// response body: each event is serialized as one line of JSON, and the reader reports EOF once
+// the sender side (the command's waiter thread) has been dropped.
+struct EventStreamReader {
+    rx: mpsc::Receiver<StreamEvent>,
+    pending: Vec<u8>,
+}
+
+impl EventStreamReader {
+    fn new(rx: mpsc::Receiver<StreamEvent>) -> Self {
+        Self { rx, pending: Vec::new() }
+    }
+}
+
+impl Read for EventStreamReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.pending.is_empty() {
+            match self.rx.recv() {
+                Ok(event) => {
+                    let mut line = serde_json::to_vec(&event).unwrap();
+                    line.push(b'\n');
+                    self.pending = line;
+                }
+                Err(_) => return Ok(0),
+            }
+        }
+
+        let n = std::cmp::min(buf.len(), self.pending.len());
+        buf[..n].copy_from_slice(&self.pending[..n]);
+        self.pending.drain(..n);
+        Ok(n)
+    }
 }
 
 fn main() {
@@ -29,12 +658,196 @@ fn main() {
     let server = Server::http("0.0.0.0:8084").unwrap();
     
     // Create a shared state for storing command results
-    let command_results: Arc<Mutex<HashMap<String, CommandResponse>>> = 
+    let command_results: Arc<Mutex<HashMap<String, CommandResponse>>> =
         Arc::new(Mutex::new(HashMap::new()));
-    
+
     // Request counter to generate unique IDs
     let counter = Arc::new(Mutex::new(0));
-    
+
+    // Pre-shared keys authenticating `/execute` requests, keyed by caller identity.
+    let psks = load_psks();
+    if psks.is_empty() {
+        println!("Warning: COMMAND_EXECUTOR_PSKS not set; /execute will reject all requests");
+    }
+
+    // Job queue consumed by the worker pool below: /execute enqueues a command and returns
+    // immediately, instead of running it inline on the single request-handling thread.
+    let (job_tx, job_rx) = mpsc::channel::<(String, CommandRequest, Option<String>)>();
+    let job_rx = Arc::new(Mutex::new(job_rx));
+
+    // Commands currently being executed by the worker pool, keyed by cmd_id, so
+    // `DELETE /jobs/:id` can find and kill one.
+    let running_jobs: Arc<Mutex<HashMap<String, RunningJob>>> = Arc::new(Mutex::new(HashMap::new()));
+
+    // Live pty-backed interactive sessions created by `POST /sessions`, keyed by session id.
+    let sessions: Arc<Mutex<HashMap<String, PtySession>>> = Arc::new(Mutex::new(HashMap::new()));
+    let session_counter = Arc::new(Mutex::new(0));
+
+    // Results of `/run-script` jobs, kept separate from `command_results` since a script's job id
+    // lives in its own "script-N" namespace and aggregates many steps instead of one command.
+    let script_results: Arc<Mutex<HashMap<String, ScriptJobResponse>>> = Arc::new(Mutex::new(HashMap::new()));
+    let script_counter = Arc::new(Mutex::new(0));
+
+    // Job queue consumed by the script worker pool below, mirroring the `/execute` queue above.
+    let (script_tx, script_rx) = mpsc::channel::<(String, RunScriptRequest, Option<String>)>();
+    let script_rx = Arc::new(Mutex::new(script_rx));
+
+    for _ in 0..SCRIPT_WORKER_THREADS {
+        let script_rx = script_rx.clone();
+        let script_results = script_results.clone();
+        let running_jobs = running_jobs.clone();
+        thread::spawn(move || loop {
+            let job = script_rx.lock().unwrap().recv();
+            let (job_id, script_req, client_id) = match job {
+                Ok(job) => job,
+                Err(_) => break, // script_tx was dropped; nothing left to work on
+            };
+
+            if let Some(entry) = script_results.lock().unwrap().get_mut(&job_id) {
+                entry.status = "running".to_string();
+            }
+
+            let deadline = script_req.timeout_ms.map(|ms| Instant::now() + Duration::from_millis(ms));
+            let cancelled = Arc::new(AtomicBool::new(false));
+            let (status, steps) = run_lua_script(
+                &job_id,
+                &script_req.script,
+                script_req.working_dir.clone(),
+                deadline,
+                cancelled,
+                running_jobs.clone(),
+            );
+
+            let response = ScriptJobResponse { id: job_id.clone(), status, steps, client_id };
+            script_results.lock().unwrap().insert(job_id, response);
+        });
+    }
+
+    for _ in 0..EXECUTE_WORKER_THREADS {
+        let job_rx = job_rx.clone();
+        let command_results = command_results.clone();
+        let running_jobs = running_jobs.clone();
+        let psks = psks.clone();
+        thread::spawn(move || loop {
+            let job = job_rx.lock().unwrap().recv();
+            let (cmd_id, command_req, client_id) = match job {
+                Ok(job) => job,
+                Err(_) => break, // job_tx was dropped; nothing left to work on
+            };
+            let callback_url = command_req.callback_url.clone();
+
+            if let Some(entry) = command_results.lock().unwrap().get_mut(&cmd_id) {
+                entry.status = "running".to_string();
+            }
+
+            let mut cmd = Command::new(&command_req.command);
+            if let Some(args) = &command_req.args {
+                cmd.args(args);
+            }
+            if let Some(dir) = &command_req.working_dir {
+                cmd.current_dir(dir);
+            }
+            cmd.stdout(Stdio::piped());
+            cmd.stderr(Stdio::piped());
+
+            let mut child = match cmd.spawn() {
+                Ok(child) => child,
+                Err(e) => {
+                    let response = CommandResponse {
+                        id: cmd_id.clone(),
+                        status: "failed".to_string(),
+                        stdout: "".to_string(),
+                        stderr: format!("Failed to execute command: {}", e),
+                        exit_code: -1,
+                        client_id,
+                        artifacts: Vec::new(),
+                    };
+                    if let Some(callback_url) = callback_url.clone() {
+                        let response = response.clone();
+                        let psks = psks.clone();
+                        thread::spawn(move || notify_webhook(&callback_url, &response, &psks));
+                    }
+                    command_results.lock().unwrap().insert(cmd_id, response);
+                    continue;
+                }
+            };
+            let stdout_pipe = child.stdout.take().expect("stdout was piped");
+            let stderr_pipe = child.stderr.take().expect("stderr was piped");
+            let job_dir = job_artifact_dir(&cmd_id);
+            let _ = std::fs::create_dir_all(&job_dir);
+            let stdout_path = job_dir.join("stdout");
+            let stderr_path = job_dir.join("stderr");
+            let stdout_thread = spawn_capturing_reader(stdout_pipe, stdout_path.clone());
+            let stderr_thread = spawn_capturing_reader(stderr_pipe, stderr_path.clone());
+
+            let child = Arc::new(Mutex::new(child));
+            let cancelled = Arc::new(AtomicBool::new(false));
+            running_jobs.lock().unwrap().insert(
+                cmd_id.clone(),
+                RunningJob { child: child.clone(), cancelled: cancelled.clone() },
+            );
+
+            let deadline = command_req.timeout_ms.map(Duration::from_millis);
+            let start = Instant::now();
+            let outcome = loop {
+                if let Ok(Some(exit_status)) = child.lock().unwrap().try_wait() {
+                    if cancelled.load(Ordering::SeqCst) {
+                        break JobOutcome::Cancelled;
+                    }
+                    break JobOutcome::Completed(exit_status);
+                }
+                if let Some(deadline) = deadline {
+                    if start.elapsed() >= deadline {
+                        let mut child = child.lock().unwrap();
+                        let _ = child.kill();
+                        let _ = child.wait();
+                        break JobOutcome::TimedOut;
+                    }
+                }
+                thread::sleep(Duration::from_millis(25));
+            };
+            running_jobs.lock().unwrap().remove(&cmd_id);
+
+            // The pipes close once the child exits (or is killed), letting the reader threads
+            // drain whatever output is left and return.
+            let _ = stdout_thread.join();
+            let _ = stderr_thread.join();
+            let stdout = std::fs::read_to_string(&stdout_path).unwrap_or_default();
+            let stderr = std::fs::read_to_string(&stderr_path).unwrap_or_default();
+
+            let mut artifacts = Vec::new();
+            artifacts.extend(artifact_info_for(&stdout_path, "stdout"));
+            artifacts.extend(artifact_info_for(&stderr_path, "stderr"));
+            artifacts.extend(collect_glob_artifacts(
+                &job_dir,
+                command_req.working_dir.as_deref(),
+                command_req.artifact_paths.as_deref(),
+            ));
+
+            let response = match outcome {
+                JobOutcome::Completed(exit_status) => {
+                    let exit_code = exit_status.code().unwrap_or(-1);
+                    let status = if exit_status.success() { "success" } else { "failed" };
+                    CommandResponse { id: cmd_id.clone(), status: status.to_string(), stdout, stderr, exit_code, client_id, artifacts }
+                }
+                JobOutcome::TimedOut => {
+                    CommandResponse { id: cmd_id.clone(), status: "timeout".to_string(), stdout, stderr, exit_code: -1, client_id, artifacts }
+                }
+                JobOutcome::Cancelled => {
+                    CommandResponse { id: cmd_id.clone(), status: "cancelled".to_string(), stdout, stderr, exit_code: -1, client_id, artifacts }
+                }
+            };
+
+            if let Some(callback_url) = callback_url {
+                let response = response.clone();
+                let psks = psks.clone();
+                thread::spawn(move || notify_webhook(&callback_url, &response, &psks));
+            }
+
+            command_results.lock().unwrap().insert(cmd_id, response);
+        });
+    }
+
     // Process each incoming request
     for mut request in server.incoming_requests() {
         // Get the URL and method before doing anything else
@@ -47,14 +860,23 @@ fn main() {
             continue;
         }
         
-        // Handle execute command
+        // Handle execute command: enqueues the command for the worker pool and returns
+        // immediately with a pending job id, instead of running it inline on this thread.
         if url == "/execute" && method == Method::Post {
             let mut content = String::new();
-            if let Err(_) = request.as_reader().read_to_string(&mut content) {
+            if request.as_reader().read_to_string(&mut content).is_err() {
                 let _ = request.respond(Response::from_string("Failed to read request body").with_status_code(400));
                 continue;
             }
-            
+
+            let client_id = match authenticate(&request, &psks, content.as_bytes()) {
+                Some(client_id) => client_id,
+                None => {
+                    let _ = request.respond(Response::from_string("Unauthorized").with_status_code(401));
+                    continue;
+                }
+            };
+
             let command_req: CommandRequest = match serde_json::from_str(&content) {
                 Ok(req) => req,
                 Err(_) => {
@@ -62,97 +884,552 @@ fn main() {
                     continue;
                 }
             };
-            
-            // Build the command
+
+            // Generate command ID
+            let cmd_id = {
+                let mut count = counter.lock().unwrap();
+                *count += 1;
+                format!("cmd-{}", *count)
+            };
+
+            let pending = CommandResponse {
+                id: cmd_id.clone(),
+                status: "pending".to_string(),
+                stdout: "".to_string(),
+                stderr: "".to_string(),
+                exit_code: 0,
+                client_id: Some(client_id.clone()),
+                artifacts: Vec::new(),
+            };
+            command_results.lock().unwrap().insert(cmd_id.clone(), pending.clone());
+
+            let _ = job_tx.send((cmd_id, command_req, Some(client_id)));
+
+            // Send response
+            let json = serde_json::to_string(&pending).unwrap();
+            let content_type = Header::from_bytes("Content-Type", "application/json").unwrap();
+            let _ = request.respond(Response::from_string(json).with_status_code(202).with_header(content_type));
+            continue;
+        }
+
+        // Handle job listing: lets callers discover in-flight and completed work without
+        // knowing an id up front.
+        if url == "/jobs" && method == Method::Get {
+            let mut content = String::new();
+            let _ = request.as_reader().read_to_string(&mut content);
+            if authenticate(&request, &psks, content.as_bytes()).is_none() {
+                let _ = request.respond(Response::from_string("Unauthorized").with_status_code(401));
+                continue;
+            }
+
+            let jobs: Vec<CommandResponse> = command_results.lock().unwrap().values().cloned().collect();
+            let json = serde_json::to_string(&jobs).unwrap();
+            let content_type = Header::from_bytes("Content-Type", "application/json").unwrap();
+            let _ = request.respond(Response::from_string(json).with_header(content_type));
+            continue;
+        }
+
+        // Handle cancellation: kills a still-running job. The worker loop notices the kill and
+        // records `status:"cancelled"` itself once it observes the child exit.
+        if url.starts_with("/jobs/") && method == Method::Delete {
+            let mut content = String::new();
+            let _ = request.as_reader().read_to_string(&mut content);
+            if authenticate(&request, &psks, content.as_bytes()).is_none() {
+                let _ = request.respond(Response::from_string("Unauthorized").with_status_code(401));
+                continue;
+            }
+
+            let cmd_id = url.trim_start_matches("/jobs/").to_string();
+            let running_job = running_jobs.lock().unwrap().get(&cmd_id).map(|job| (job.child.clone(), job.cancelled.clone()));
+
+            match running_job {
+                Some((child, cancelled)) => {
+                    cancelled.store(true, Ordering::SeqCst);
+                    let _ = child.lock().unwrap().kill();
+                    let _ = request.respond(Response::from_string("Cancellation requested"));
+                }
+                None => {
+                    let error_msg = format!("No running job with ID {}", cmd_id);
+                    let _ = request.respond(Response::from_string(error_msg).with_status_code(404));
+                }
+            }
+            continue;
+        }
+
+        // Handle interactive pty session creation: allocates a pseudo-terminal for the child and
+        // streams its combined output back as the same ndjson event framing as /execute/stream.
+        // The session id is returned in the `X-Session-Id` response header; send stdin to it via
+        // `POST /sessions/:id/stdin`.
+        if url == "/sessions" && method == Method::Post {
+            let mut content = String::new();
+            if request.as_reader().read_to_string(&mut content).is_err() {
+                let _ = request.respond(Response::from_string("Failed to read request body").with_status_code(400));
+                continue;
+            }
+
+            if authenticate(&request, &psks, content.as_bytes()).is_none() {
+                let _ = request.respond(Response::from_string("Unauthorized").with_status_code(401));
+                continue;
+            }
+
+            let command_req: CommandRequest = match serde_json::from_str(&content) {
+                Ok(req) => req,
+                Err(_) => {
+                    let _ = request.respond(Response::from_string("Invalid JSON").with_status_code(400));
+                    continue;
+                }
+            };
+
+            // /sessions always allocates a pty; `pty` only exists on `CommandRequest` so a caller
+            // can say so explicitly, and an explicit `false` here means they meant to hit
+            // /execute or /execute/stream instead.
+            if command_req.pty == Some(false) {
+                let _ = request.respond(
+                    Response::from_string("pty must not be false for /sessions").with_status_code(400),
+                );
+                continue;
+            }
+
+            let pty_system = native_pty_system();
+            let pair = match pty_system.openpty(PtySize {
+                rows: command_req.rows.unwrap_or(24),
+                cols: command_req.cols.unwrap_or(80),
+                pixel_width: 0,
+                pixel_height: 0,
+            }) {
+                Ok(pair) => pair,
+                Err(e) => {
+                    let _ = request.respond(
+                        Response::from_string(format!("Failed to allocate pty: {}", e)).with_status_code(500),
+                    );
+                    continue;
+                }
+            };
+
+            let mut cmd = CommandBuilder::new(&command_req.command);
+            if let Some(args) = &command_req.args {
+                for arg in args {
+                    cmd.arg(arg);
+                }
+            }
+            if let Some(dir) = &command_req.working_dir {
+                cmd.cwd(dir);
+            }
+
+            let mut child = match pair.slave.spawn_command(cmd) {
+                Ok(child) => child,
+                Err(e) => {
+                    let _ = request.respond(
+                        Response::from_string(format!("Failed to spawn command: {}", e)).with_status_code(500),
+                    );
+                    continue;
+                }
+            };
+            // The slave side belongs to the child now; dropping our end lets the master see EOF
+            // once the child exits.
+            drop(pair.slave);
+
+            let reader = match pair.master.try_clone_reader() {
+                Ok(reader) => reader,
+                Err(e) => {
+                    let _ = request.respond(
+                        Response::from_string(format!("Failed to read from pty: {}", e)).with_status_code(500),
+                    );
+                    continue;
+                }
+            };
+            let writer = match pair.master.take_writer() {
+                Ok(writer) => writer,
+                Err(e) => {
+                    let _ = request.respond(
+                        Response::from_string(format!("Failed to write to pty: {}", e)).with_status_code(500),
+                    );
+                    continue;
+                }
+            };
+
+            let session_id = {
+                let mut count = session_counter.lock().unwrap();
+                *count += 1;
+                format!("session-{}", *count)
+            };
+
+            sessions.lock().unwrap().insert(
+                session_id.clone(),
+                PtySession {
+                    writer: Arc::new(Mutex::new(writer)),
+                    master: Arc::new(Mutex::new(pair.master)),
+                },
+            );
+
+            let (tx, rx) = mpsc::channel();
+            let reader_thread = spawn_pipe_reader("pty", reader, tx.clone());
+            let waiter_tx = tx.clone();
+            drop(tx);
+
+            let sessions_for_waiter = sessions.clone();
+            let session_id_for_waiter = session_id.clone();
+            thread::spawn(move || {
+                let _ = reader_thread.join();
+                let exit_code = match child.wait() {
+                    Ok(status) => status.exit_code() as i32,
+                    Err(_) => -1,
+                };
+                sessions_for_waiter.lock().unwrap().remove(&session_id_for_waiter);
+                let _ = waiter_tx.send(StreamEvent::Done { exit_code });
+            });
+
+            let session_header = Header::from_bytes("X-Session-Id", session_id.as_str()).unwrap();
+            let content_type = Header::from_bytes("Content-Type", "application/x-ndjson").unwrap();
+            let _ = request.respond(Response::new(
+                StatusCode(200),
+                vec![content_type, session_header],
+                EventStreamReader::new(rx),
+                None,
+                None,
+            ));
+            continue;
+        }
+
+        // Handle stdin delivery (and in-band resize) for an interactive pty session.
+        if url.starts_with("/sessions/") && url.ends_with("/stdin") && method == Method::Post {
+            let session_id = url
+                .trim_start_matches("/sessions/")
+                .trim_end_matches("/stdin")
+                .trim_end_matches('/')
+                .to_string();
+
+            let mut content = String::new();
+            if request.as_reader().read_to_string(&mut content).is_err() {
+                let _ = request.respond(Response::from_string("Failed to read request body").with_status_code(400));
+                continue;
+            }
+
+            if authenticate(&request, &psks, content.as_bytes()).is_none() {
+                let _ = request.respond(Response::from_string("Unauthorized").with_status_code(401));
+                continue;
+            }
+
+            let session = sessions
+                .lock()
+                .unwrap()
+                .get(&session_id)
+                .map(|session| (session.writer.clone(), session.master.clone()));
+            let (writer, master) = match session {
+                Some(session) => session,
+                None => {
+                    let error_msg = format!("No session with ID {}", session_id);
+                    let _ = request.respond(Response::from_string(error_msg).with_status_code(404));
+                    continue;
+                }
+            };
+
+            // The body is newline-delimited JSON frames so a single request can carry several
+            // keystrokes (or a keystroke followed by a resize) in one round trip.
+            for line in content.lines() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                match serde_json::from_str::<StdinFrame>(line) {
+                    Ok(StdinFrame::Data { data }) => {
+                        if let Ok(bytes) = BASE64.decode(&data) {
+                            let _ = writer.lock().unwrap().write_all(&bytes);
+                        }
+                    }
+                    Ok(StdinFrame::Resize { resize }) => {
+                        let _ = master.lock().unwrap().resize(PtySize {
+                            rows: resize.rows,
+                            cols: resize.cols,
+                            pixel_width: 0,
+                            pixel_height: 0,
+                        });
+                    }
+                    Err(_) => {}
+                }
+            }
+
+            let _ = request.respond(Response::from_string("OK"));
+            continue;
+        }
+
+        // Handle streaming execute: same command semantics as /execute, but output is emitted
+        // as it's produced instead of buffered to completion. Each line of the response body is
+        // a JSON event: `{"stream":"stdout"|"stderr","data":"..."}` while the command runs, then
+        // a final `{"exit_code":N}` once it exits. The command id is returned via the
+        // `X-Command-Id` header (the body is the event stream itself) so `timeout_ms` and
+        // `DELETE /jobs/:id` work the same way they do for `/execute`.
+        if url == "/execute/stream" && method == Method::Post {
+            let mut content = String::new();
+            if request.as_reader().read_to_string(&mut content).is_err() {
+                let _ = request.respond(Response::from_string("Failed to read request body").with_status_code(400));
+                continue;
+            }
+
+            if authenticate(&request, &psks, content.as_bytes()).is_none() {
+                let _ = request.respond(Response::from_string("Unauthorized").with_status_code(401));
+                continue;
+            }
+
+            let command_req: CommandRequest = match serde_json::from_str(&content) {
+                Ok(req) => req,
+                Err(_) => {
+                    let _ = request.respond(Response::from_string("Invalid JSON").with_status_code(400));
+                    continue;
+                }
+            };
+
             let mut cmd = Command::new(&command_req.command);
-            
-            // Add arguments if provided
             if let Some(args) = &command_req.args {
                 cmd.args(args);
             }
-            
-            // Set working directory if provided
             if let Some(dir) = &command_req.working_dir {
                 cmd.current_dir(dir);
             }
-            
-            // Generate command ID
+            cmd.stdout(Stdio::piped());
+            cmd.stderr(Stdio::piped());
+
+            let mut child = match cmd.spawn() {
+                Ok(child) => child,
+                Err(e) => {
+                    let _ = request.respond(
+                        Response::from_string(format!("Failed to execute command: {}", e)).with_status_code(500),
+                    );
+                    continue;
+                }
+            };
+            let stdout_pipe = child.stdout.take().expect("stdout was piped");
+            let stderr_pipe = child.stderr.take().expect("stderr was piped");
+
             let cmd_id = {
                 let mut count = counter.lock().unwrap();
                 *count += 1;
                 format!("cmd-{}", *count)
             };
-            
-            // Execute the command
-            let output = match cmd.output() {
-                Ok(output) => output,
-                Err(e) => {
-                    let response = CommandResponse {
-                        id: cmd_id,
-                        status: "error".to_string(),
-                        stdout: "".to_string(),
-                        stderr: format!("Failed to execute command: {}", e),
-                        exit_code: -1,
-                    };
-                    
-                    let json = serde_json::to_string(&response).unwrap();
-                    let _ = request.respond(Response::from_string(json).with_status_code(500));
-                    continue;
-                }
-            };
-            
-            // Process command output
-            let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-            let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-            let exit_code = output.status.code().unwrap_or(-1);
-            
-            let status = if output.status.success() {
-                "success"
-            } else {
-                "failed"
-            };
-            
-            // Create response
-            let response = CommandResponse {
-                id: cmd_id.clone(),
-                status: status.to_string(),
-                stdout,
-                stderr,
-                exit_code,
+
+            let (tx, rx) = mpsc::channel();
+            let stdout_thread = spawn_pipe_reader("stdout", stdout_pipe, tx.clone());
+            let stderr_thread = spawn_pipe_reader("stderr", stderr_pipe, tx.clone());
+            let waiter_tx = tx.clone();
+            drop(tx);
+
+            let child = Arc::new(Mutex::new(child));
+            let cancelled = Arc::new(AtomicBool::new(false));
+            running_jobs.lock().unwrap().insert(
+                cmd_id.clone(),
+                RunningJob { child: child.clone(), cancelled: cancelled.clone() },
+            );
+
+            let deadline = command_req.timeout_ms.map(Duration::from_millis);
+            let start = Instant::now();
+            let running_jobs = running_jobs.clone();
+            let waiter_cmd_id = cmd_id.clone();
+
+            // Polls rather than blocking on `child.wait()` so this thread can also notice a
+            // timeout or a `DELETE /jobs/:id` cancellation, the same way the `/execute` worker
+            // loop does. The pipe reader threads see EOF once the child exits or is killed.
+            thread::spawn(move || {
+                let exit_code = loop {
+                    if let Ok(Some(status)) = child.lock().unwrap().try_wait() {
+                        break status.code().unwrap_or(-1);
+                    }
+                    if let Some(deadline) = deadline {
+                        if start.elapsed() >= deadline {
+                            let mut child = child.lock().unwrap();
+                            let _ = child.kill();
+                            let _ = child.wait();
+                            break -1;
+                        }
+                    }
+                    thread::sleep(Duration::from_millis(25));
+                };
+                running_jobs.lock().unwrap().remove(&waiter_cmd_id);
+                let _ = stdout_thread.join();
+                let _ = stderr_thread.join();
+                let _ = waiter_tx.send(StreamEvent::Done { exit_code });
+            });
+
+            let command_id_header = Header::from_bytes("X-Command-Id", cmd_id.as_str()).unwrap();
+            let content_type = Header::from_bytes("Content-Type", "application/x-ndjson").unwrap();
+            let _ = request.respond(Response::new(
+                StatusCode(200),
+                vec![content_type, command_id_header],
+                EventStreamReader::new(rx),
+                None,
+                None,
+            ));
+            continue;
+        }
+
+        // Handle script execution: runs a Lua recipe through its own worker pool and returns a
+        // job id immediately, the same enqueue-and-202 shape as /execute. Each `run()` call the
+        // recipe makes becomes one step in the job's aggregate result, retrievable afterwards
+        // from the same `/results/{id}` endpoint as a single-command job.
+        if url == "/run-script" && method == Method::Post {
+            let mut content = String::new();
+            if request.as_reader().read_to_string(&mut content).is_err() {
+                let _ = request.respond(Response::from_string("Failed to read request body").with_status_code(400));
+                continue;
+            }
+
+            let client_id = match authenticate(&request, &psks, content.as_bytes()) {
+                Some(client_id) => client_id,
+                None => {
+                    let _ = request.respond(Response::from_string("Unauthorized").with_status_code(401));
+                    continue;
+                }
             };
-            
-            // Store the result
-            command_results.lock().unwrap().insert(cmd_id, response.clone());
-            
-            // Send response
-            let json = serde_json::to_string(&response).unwrap();
-            
-            // Create a proper header object directly
+
+            let script_req: RunScriptRequest = match serde_json::from_str(&content) {
+                Ok(req) => req,
+                Err(_) => {
+                    let _ = request.respond(Response::from_string("Invalid JSON").with_status_code(400));
+                    continue;
+                }
+            };
+
+            let job_id = {
+                let mut count = script_counter.lock().unwrap();
+                *count += 1;
+                format!("script-{}", *count)
+            };
+
+            let pending = ScriptJobResponse {
+                id: job_id.clone(),
+                status: "pending".to_string(),
+                steps: Vec::new(),
+                client_id: Some(client_id.clone()),
+            };
+            script_results.lock().unwrap().insert(job_id.clone(), pending.clone());
+
+            let _ = script_tx.send((job_id, script_req, Some(client_id)));
+
+            let json = serde_json::to_string(&pending).unwrap();
             let content_type = Header::from_bytes("Content-Type", "application/json").unwrap();
-            
-            let _ = request.respond(Response::from_string(json).with_header(content_type));
+            let _ = request.respond(Response::from_string(json).with_status_code(202).with_header(content_type));
             continue;
         }
-        
-        // Handle results retrieval
-        if url.starts_with("/results/") && method == Method::Get {
-            // Extract the command ID from the URL
-            let cmd_id = url.trim_start_matches("/results/").to_string();
-            
-            // Get result from storage
-            let result = command_results.lock().unwrap().get(&cmd_id).cloned();
-            
-            match result {
-                Some(response) => {
-                    let json = serde_json::to_string(&response).unwrap();
-                    let content_type = Header::from_bytes("Content-Type", "application/json").unwrap();
-                    let _ = request.respond(Response::from_string(json).with_header(content_type));
-                }
+
+        // Handle artifact retrieval: serves one file a job collected (its captured stdout/stderr,
+        // or a file matched by `artifact_paths`), honoring a `Range` header so large artifacts
+        // can be fetched in chunks instead of all at once.
+        if url.starts_with("/results/") && url.contains("/artifacts/") && method == Method::Get {
+            let mut content = String::new();
+            let _ = request.as_reader().read_to_string(&mut content);
+            if authenticate(&request, &psks, content.as_bytes()).is_none() {
+                let _ = request.respond(Response::from_string("Unauthorized").with_status_code(401));
+                continue;
+            }
+
+            let rest = url.trim_start_matches("/results/");
+            let mut parts = rest.splitn(2, "/artifacts/");
+            let cmd_id = parts.next().unwrap_or("").to_string();
+            let artifact_name = parts.next().unwrap_or("").to_string();
+
+            let artifact = command_results
+                .lock()
+                .unwrap()
+                .get(&cmd_id)
+                .and_then(|response| response.artifacts.iter().find(|a| a.name == artifact_name).cloned());
+
+            let artifact = match artifact {
+                Some(artifact) => artifact,
                 None => {
-                    let error_msg = format!("Command result with ID {} not found", cmd_id);
+                    let error_msg = format!("No artifact named {} for job {}", artifact_name, cmd_id);
                     let _ = request.respond(Response::from_string(error_msg).with_status_code(404));
+                    continue;
+                }
+            };
+
+            let mut file = match std::fs::File::open(&artifact.path) {
+                Ok(file) => file,
+                Err(_) => {
+                    let _ = request.respond(Response::from_string("Artifact file missing").with_status_code(404));
+                    continue;
+                }
+            };
+
+            let range_header = request
+                .headers()
+                .iter()
+                .find(|h| h.field.as_str().as_str().eq_ignore_ascii_case("Range"))
+                .map(|h| h.value.as_str().to_string());
+
+            let content_type = Header::from_bytes("Content-Type", artifact.content_type.as_bytes()).unwrap();
+            let accept_ranges = Header::from_bytes("Accept-Ranges", "bytes").unwrap();
+
+            match range_header.as_deref().and_then(parse_range_header) {
+                Some((start, end)) => {
+                    let last_byte = artifact.size.saturating_sub(1);
+                    let end = end.unwrap_or(last_byte).min(last_byte);
+                    if artifact.size == 0 || start > end || start >= artifact.size {
+                        let _ = request.respond(Response::from_string("Range not satisfiable").with_status_code(416));
+                        continue;
+                    }
+                    let len = (end - start + 1) as usize;
+                    if file.seek(SeekFrom::Start(start)).is_err() {
+                        let _ = request.respond(Response::from_string("Failed to read artifact").with_status_code(500));
+                        continue;
+                    }
+                    let mut buf = vec![0u8; len];
+                    if file.read_exact(&mut buf).is_err() {
+                        let _ = request.respond(Response::from_string("Failed to read artifact").with_status_code(500));
+                        continue;
+                    }
+                    let content_range = Header::from_bytes(
+                        "Content-Range",
+                        format!("bytes {}-{}/{}", start, end, artifact.size).as_bytes(),
+                    )
+                    .unwrap();
+                    let _ = request.respond(
+                        Response::from_data(buf)
+                            .with_status_code(206)
+                            .with_header(content_type)
+                            .with_header(accept_ranges)
+                            .with_header(content_range),
+                    );
                 }
+                None => {
+                    let mut buf = Vec::new();
+                    if file.read_to_end(&mut buf).is_err() {
+                        let _ = request.respond(Response::from_string("Failed to read artifact").with_status_code(500));
+                        continue;
+                    }
+                    let _ = request.respond(
+                        Response::from_data(buf).with_header(content_type).with_header(accept_ranges),
+                    );
+                }
+            }
+            continue;
+        }
+
+        // Handle results retrieval: looks up a single-command job first, then falls back to a
+        // script job, since the two live in separate "cmd-N"/"script-N" id namespaces.
+        if url.starts_with("/results/") && method == Method::Get {
+            let mut content = String::new();
+            let _ = request.as_reader().read_to_string(&mut content);
+            if authenticate(&request, &psks, content.as_bytes()).is_none() {
+                let _ = request.respond(Response::from_string("Unauthorized").with_status_code(401));
+                continue;
+            }
+
+            let cmd_id = url.trim_start_matches("/results/").to_string();
+
+            if let Some(response) = command_results.lock().unwrap().get(&cmd_id).cloned() {
+                let json = serde_json::to_string(&response).unwrap();
+                let content_type = Header::from_bytes("Content-Type", "application/json").unwrap();
+                let _ = request.respond(Response::from_string(json).with_header(content_type));
+                continue;
             }
+
+            if let Some(response) = script_results.lock().unwrap().get(&cmd_id).cloned() {
+                let json = serde_json::to_string(&response).unwrap();
+                let content_type = Header::from_bytes("Content-Type", "application/json").unwrap();
+                let _ = request.respond(Response::from_string(json).with_header(content_type));
+                continue;
+            }
+
+            let error_msg = format!("Command result with ID {} not found", cmd_id);
+            let _ = request.respond(Response::from_string(error_msg).with_status_code(404));
             continue;
         }
         
@@ -160,3 +1437,95 @@ fn main() {
         let _ = request.respond(Response::from_string("Not found").with_status_code(404));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sign(psk: &str, body: &[u8]) -> String {
+        let mut mac = HmacSha256::new_from_slice(psk.as_bytes()).unwrap();
+        mac.update(body);
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    fn psks() -> HashMap<String, String> {
+        let mut psks = HashMap::new();
+        psks.insert("client-1".to_string(), "super-secret".to_string());
+        psks
+    }
+
+    #[test]
+    fn verify_signature_accepts_a_correctly_signed_body() {
+        let psks = psks();
+        let body = b"{\"command\":\"echo hi\"}";
+        let signature = sign("super-secret", body);
+        assert!(verify_signature(&psks, "client-1", &signature, body));
+    }
+
+    #[test]
+    fn verify_signature_rejects_a_signature_over_a_different_body() {
+        let psks = psks();
+        let signature = sign("super-secret", b"original body");
+        assert!(!verify_signature(&psks, "client-1", &signature, b"tampered body"));
+    }
+
+    #[test]
+    fn verify_signature_rejects_an_unknown_client_id() {
+        let psks = psks();
+        let body = b"payload";
+        let signature = sign("super-secret", body);
+        assert!(!verify_signature(&psks, "no-such-client", &signature, body));
+    }
+
+    #[test]
+    fn verify_signature_rejects_malformed_hex() {
+        let psks = psks();
+        assert!(!verify_signature(&psks, "client-1", "not-hex", b"payload"));
+    }
+
+    #[test]
+    fn run_lua_script_interrupts_a_busy_loop_that_never_calls_run() {
+        let deadline = Instant::now() + Duration::from_millis(200);
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let running_jobs = Arc::new(Mutex::new(HashMap::new()));
+
+        let start = Instant::now();
+        let (status, steps) = run_lua_script(
+            "busy-loop-job",
+            "local i = 0 while true do i = i + 1 end",
+            None,
+            Some(deadline),
+            cancelled,
+            running_jobs,
+        );
+
+        assert!(start.elapsed() < Duration::from_secs(5), "hook failed to interrupt the loop");
+        assert_eq!(status, "timeout");
+        assert!(steps.is_empty());
+    }
+
+    #[test]
+    fn collect_glob_artifacts_ignores_patterns_that_escape_working_dir() {
+        let tmp = std::env::temp_dir().join(format!(
+            "command_executor_test_{}",
+            std::process::id()
+        ));
+        let working_dir = tmp.join("workdir");
+        let job_dir = tmp.join("job");
+        std::fs::create_dir_all(&working_dir).unwrap();
+        std::fs::create_dir_all(&job_dir).unwrap();
+
+        let outside_secret = tmp.join("secret.txt");
+        std::fs::write(&outside_secret, b"do not copy me").unwrap();
+        let inside_file = working_dir.join("inside.txt");
+        std::fs::write(&inside_file, b"safe to copy").unwrap();
+
+        let patterns = vec![outside_secret.to_str().unwrap().to_string(), "inside.txt".to_string()];
+        let artifacts = collect_glob_artifacts(&job_dir, working_dir.to_str(), Some(&patterns));
+
+        let names: Vec<&str> = artifacts.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, vec!["inside.txt"]);
+
+        std::fs::remove_dir_all(&tmp).ok();
+    }
+}